@@ -0,0 +1,109 @@
+//! Tokenizer throughput, with separators found by vector instructions and
+//! byte by byte (`--no-simd`), against the tokenizers they replaced: splitting
+//! a `BufRead` at spaces, and testing every byte against the separators.
+//!
+//! Run with `cargo bench --bench tokenize`.
+
+use std::hint::black_box;
+use std::io::{BufRead, BufReader};
+use std::time::Instant;
+
+use bigfilestat::{for_each_token, for_each_token_in, Separators};
+
+const SIZE: usize = 256 << 20;
+const ROUNDS: usize = 5;
+
+/// Values of 1 to 16 characters with one or two separators between them.
+fn input() -> Vec<u8> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut data = Vec::with_capacity(SIZE + 32);
+    while data.len() < SIZE {
+        let r = next();
+        let digits = 1 + (r % 16) as usize;
+        data.extend((0..digits).map(|i| b'0' + ((r >> (8 + 3 * i)) % 10) as u8));
+        data.extend_from_slice(if r >> 60 == 0 { b"\r\n" } else { b" " });
+    }
+    data
+}
+
+fn bench(name: &str, data: &[u8], mut run: impl FnMut() -> usize) {
+    let mut best = f64::INFINITY;
+    let mut tokens = 0;
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        tokens = black_box(run());
+        best = best.min(start.elapsed().as_secs_f64());
+    }
+    println!("{name:<28} {:>6.2} GB/s  ({tokens} tokens)", data.len() as f64 / best / 1e9);
+}
+
+fn count_in(data: &[u8], separators: &Separators) -> usize {
+    let mut n = 0;
+    for_each_token_in(data, 0, separators, |token, _| {
+        black_box(token);
+        n += 1;
+        Ok::<_, ()>(())
+    })
+    .unwrap();
+    n
+}
+
+fn count_read(data: &[u8], separators: &Separators) -> usize {
+    let mut n = 0;
+    for_each_token(data, separators, |token, _| {
+        black_box(token);
+        n += 1;
+        Ok::<_, std::io::Error>(())
+    })
+    .unwrap();
+    n
+}
+
+/// The first tokenizer: a copy of every token, split at single spaces only,
+/// so line breaks stay in the tokens.
+fn count_split(data: &[u8]) -> usize {
+    BufReader::new(data).split(b' ').map(|token| black_box(token.unwrap())).filter(|token| !token.is_empty()).count()
+}
+
+/// The tokenizer before separators were found in blocks: each byte is looked
+/// up in the separator set on its own.
+fn count_bytewise(data: &[u8], separators: &Separators) -> usize {
+    let (len, mut i, mut n) = (data.len(), 0, 0);
+    loop {
+        while i < len && separators.contains(data[i]) {
+            i += 1;
+        }
+        let start = i;
+        while i < len && !separators.contains(data[i]) {
+            i += 1;
+        }
+        if start < i {
+            black_box(&data[start..i]);
+            n += 1;
+        }
+        if i == len {
+            return n;
+        }
+    }
+}
+
+fn main() {
+    let data = input();
+    let simd = Separators::default();
+    let scalar = Separators::default().scalar();
+    let extra = Separators::new(b",;");
+
+    bench("before: BufRead::split", &data, || count_split(&data));
+    bench("before: byte at a time", &data, || count_bytewise(&data, &simd));
+    bench("in memory, simd", &data, || count_in(&data, &simd));
+    bench("in memory, scalar", &data, || count_in(&data, &scalar));
+    bench("in memory, simd ',;'", &data, || count_in(&data, &extra));
+    bench("buffered, simd", &data, || count_read(&data, &simd));
+    bench("buffered, scalar", &data, || count_read(&data, &scalar));
+}
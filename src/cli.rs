@@ -0,0 +1,323 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use bigfilestat::{Codec, Column, Decompress, Element, Format, Interpolation, NonFinite, OnError, ReadOptions, Separators, Stats};
+use crate::report::Table;
+
+pub const USAGE: &str = "\
+Usage: bigfilestat [OPTIONS] [FILE]...
+
+Computes statistics over files of whitespace-separated numbers, raw binary
+values or NumPy arrays (see --format), columns of CSV and TSV files, or
+fields of JSON Lines. Input compressed with gzip, zstd or xz is
+decompressed on the fly. With no FILE, or when FILE is -, reads standard input.
+When no statistic is selected, all of them are computed.
+
+Statistics:
+      --len                 number of values
+      --minmax              smallest and largest value
+      --mean                arithmetic mean
+      --variance            population and sample variance, standard deviation
+      --skewness            skewness
+      --kurtosis            excess kurtosis
+      --median              median (needs extra passes over the input)
+      --percentiles <P,...> percentiles between 0 and 100, e.g. 1,25,99.9
+                            (need extra passes over the input)
+      --iqr                 interquartile range, P75 - P25
+      --interpolation <M>   how percentiles between two values are computed:
+                            linear (default), lower, higher, midpoint, nearest
+      --approx-percentiles <P,...>
+                            percentiles estimated from a KLL sketch during the
+                            single scan, without extra passes
+      --accuracy <EPS>      rank error allowed for approximate percentiles
+                            (default 0.01, i.e. 1%)
+      --tails <N>           first and last N values
+      --covariance          sample covariance of every pair of columns
+      --correlation         Pearson correlation of every pair of columns
+                            (both need columns, and only take rows that have
+                            values in both columns of a pair)
+
+Input:
+      --format <FMT>        how values are stored: text (default), or a raw array
+                            of binary values: f32le, f32be, f64le, f64be, i8, u8,
+                            i16le, i16be, ..., u64le, u64be; or npy for NumPy
+                            array files
+      --csv, --tsv          read comma- or tab-separated records; quoted fields
+                            may hold delimiters, quotes (as \"\") and line breaks
+      --column <COL>        field of the records to summarize, by header name or
+                            1-based number; may be repeated (default: every
+                            field that holds a number in the first record)
+      --header, --no-header whether the first record names the fields (default:
+                            guessed from whether it holds text in a column
+                            where the second record holds a number)
+      --jsonl               read one JSON object per line
+      --field <PATH>        field of the objects to summarize, as a dotted path
+                            such as resp.timing.total (a number in the path
+                            picks an array element); may be repeated. A missing
+                            or non-numeric field is a bad value (see --on-error)
+      --per-column          summarize every column of a multi-dimensional npy
+                            array on its own instead of all values together
+      --separators <CHARS>  also split values on these characters, e.g. ',;'
+      --on-error <POLICY>   what to do with a bad value: fail (default), skip,
+                            or warn (skip and print a warning)
+      --non-finite <POLICY> what to do with NaN and inf: include, exclude,
+                            or error (default, handled like a bad value)
+      --decompress <MODE>   auto (default) decompresses gzip, zstd and xz input,
+                            told by its first bytes; none reads input as it is;
+                            gzip, zstd or xz decompress all input with that codec
+      --no-mmap             read regular files through a buffer instead of
+                            mapping them into memory
+      --no-simd             find separators byte by byte instead of with
+                            vector instructions
+  -j, --threads <N>         threads scanning a regular file (default: one per core)
+
+Output:
+      --output <FMT>        text (default), or json: one document for other
+                            programs to read, with the tool version and, per
+                            input, the path, value counts, skipped values, the
+                            statistics of every column and the seconds taken
+                            by the scan and the selection passes (per pass,
+                            not per statistic: one scan computes them all);
+                            or csv, tsv, markdown: one table with a row per
+                            input (or per column of an input) and a column per
+                            statistic; tails and matrices are left out;
+                            or openmetrics: gauges and a summary labelled with
+                            the file and column, for the Prometheus
+                            node_exporter textfile collector; the summary has
+                            the sum of the values, so the mean comes with it
+
+Options:
+  -h, --help                print this help and exit
+  -V, --version             print version and exit
+";
+
+/// How the statistics are printed.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum Output {
+    #[default]
+    Text,
+    Json,
+    Table(Table),
+    OpenMetrics,
+}
+
+pub struct Args {
+    pub inputs: Vec<PathBuf>,
+    pub stats: Stats,
+    pub options: ReadOptions,
+    pub output: Output,
+}
+
+pub enum Command {
+    Run(Box<Args>),
+    Help,
+    Version,
+}
+
+fn parse_percentiles(list: &str) -> Result<Vec<f64>, String> {
+    list.split(',')
+        .map(|p| match p.trim().parse::<f64>() {
+            Ok(p) if (0.0..=100.0).contains(&p) => Ok(p),
+            _ => Err(format!("invalid percentile '{p}', expected a number between 0 and 100")),
+        })
+        .collect()
+}
+
+/// Parses the arguments that follow the program name. File names are taken
+/// as they are; options and their values must be UTF-8.
+pub fn parse<I: IntoIterator<Item = OsString>>(args: I) -> Result<Command, String> {
+    let mut args = args.into_iter();
+    let mut inputs = Vec::new();
+    let mut stats = Stats::default();
+    let mut options = ReadOptions::default();
+    let mut output = Output::default();
+    let mut simd = true;
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            inputs.extend(args.by_ref().map(PathBuf::from));
+            break;
+        }
+        if !arg.as_encoded_bytes().starts_with(b"-") || arg == "-" {
+            inputs.push(PathBuf::from(arg));
+            continue;
+        }
+        let arg = arg.into_string().map_err(|arg| format!("unknown option '{}'", arg.to_string_lossy()))?;
+
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        const WITH_VALUE: &[&str] = &[
+            "--tails", "--percentiles", "--interpolation", "--approx-percentiles", "--accuracy", "--format", "--column", "--field", "--separators", "--on-error", "--non-finite", "--decompress", "--output", "-j", "--threads",
+        ];
+        if inline_value.is_some() && !WITH_VALUE.contains(&name) {
+            return Err(format!("option '{name}' does not take a value"));
+        }
+        let mut value = || match inline_value.clone() {
+            Some(value) => Ok(value),
+            None => args.next()
+                .ok_or_else(|| format!("option '{name}' requires a value"))?
+                .into_string()
+                .map_err(|value| format!("invalid value '{}' for '{name}'", value.to_string_lossy())),
+        };
+
+        match name {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "--len" => stats.len = true,
+            "--minmax" => stats.min_max = true,
+            "--mean" => stats.mean = true,
+            "--variance" => stats.variance = true,
+            "--skewness" => stats.skewness = true,
+            "--kurtosis" => stats.kurtosis = true,
+            "--median" => stats.median = true,
+            "--iqr" => stats.iqr = true,
+            "--covariance" => stats.covariance = true,
+            "--correlation" => stats.correlation = true,
+            "--percentiles" => stats.percentiles.extend(parse_percentiles(&value()?)?),
+            "--approx-percentiles" => stats.approx_percentiles.extend(parse_percentiles(&value()?)?),
+            "--accuracy" => {
+                let value = value()?;
+                match value.parse::<f64>() {
+                    Ok(eps) if eps > 0.0 && eps < 1.0 => stats.accuracy = Some(eps),
+                    _ => return Err(format!("invalid value '{value}' for '--accuracy', expected a number in (0, 1)")),
+                }
+            }
+            "--interpolation" => {
+                stats.interpolation = match value()?.as_str() {
+                    "linear" => Interpolation::Linear,
+                    "lower" => Interpolation::Lower,
+                    "higher" => Interpolation::Higher,
+                    "midpoint" => Interpolation::Midpoint,
+                    "nearest" => Interpolation::Nearest,
+                    other => return Err(format!("invalid value '{other}' for '--interpolation'")),
+                };
+            }
+            "--tails" => {
+                let value = value()?;
+                let n = value.parse().map_err(|_| format!("invalid value '{value}' for '--tails'"))?;
+                stats.tails = Some(n);
+            }
+            "--format" => {
+                options.format = match value()?.as_str() {
+                    "text" => Format::Text,
+                    "npy" => Format::Npy,
+                    other => match Element::from_name(other) {
+                        Some(element) => Format::Binary(element),
+                        None => return Err(format!("invalid value '{other}' for '--format'")),
+                    },
+                };
+            }
+            "--separators" => {
+                let value = value()?;
+                if !value.is_ascii() {
+                    return Err(format!("separators must be ASCII characters, got '{value}'"));
+                }
+                options.separators = Separators::new(value.as_bytes());
+            }
+            "--csv" => options.format = Format::Delimited { delimiter: b',' },
+            "--tsv" => options.format = Format::Delimited { delimiter: b'\t' },
+            "--column" => {
+                let value = value()?;
+                options.columns.push(match value.parse::<usize>() {
+                    Ok(0) => return Err("column numbers start at 1".to_string()),
+                    Ok(n) => Column::Index(n - 1),
+                    Err(_) => Column::Name(value),
+                });
+            }
+            "--jsonl" => options.format = Format::Jsonl,
+            "--field" => {
+                let value = value()?;
+                if value.split('.').any(str::is_empty) {
+                    return Err(format!("invalid field path '{value}'"));
+                }
+                options.fields.push(value);
+            }
+            "--header" => options.header = Some(true),
+            "--no-header" => options.header = Some(false),
+            "--per-column" => options.per_column = true,
+            "--decompress" => {
+                options.decompress = match value()?.as_str() {
+                    "auto" => Decompress::Detect,
+                    "none" => Decompress::Never,
+                    other => match Codec::from_name(other) {
+                        Some(codec) => Decompress::Always(codec),
+                        None => return Err(format!("invalid value '{other}' for '--decompress'")),
+                    },
+                };
+            }
+            "--output" => {
+                output = match value()?.as_str() {
+                    "text" => Output::Text,
+                    "json" => Output::Json,
+                    "csv" => Output::Table(Table::Csv),
+                    "tsv" => Output::Table(Table::Tsv),
+                    "markdown" => Output::Table(Table::Markdown),
+                    "openmetrics" => Output::OpenMetrics,
+                    other => return Err(format!("invalid value '{other}' for '--output'")),
+                };
+            }
+            "--no-mmap" => options.mmap = false,
+            "--no-simd" => simd = false,
+            "-j" | "--threads" => {
+                let value = value()?;
+                match value.parse() {
+                    Ok(n) if n > 0 => options.threads = n,
+                    _ => return Err(format!("invalid value '{value}' for '--threads'")),
+                }
+            }
+            "--on-error" => {
+                options.on_error = match value()?.as_str() {
+                    "fail" => OnError::Fail,
+                    "skip" => OnError::Skip,
+                    "warn" => OnError::Warn,
+                    other => return Err(format!("invalid value '{other}' for '--on-error'")),
+                };
+            }
+            "--non-finite" => {
+                options.non_finite = match value()?.as_str() {
+                    "include" => NonFinite::Include,
+                    "exclude" => NonFinite::Exclude,
+                    "error" => NonFinite::Error,
+                    other => return Err(format!("invalid value '{other}' for '--non-finite'")),
+                };
+            }
+            _ => return Err(format!("unknown option '{name}'")),
+        }
+    }
+
+    let delimited = matches!(options.format, Format::Delimited { .. });
+    if !options.columns.is_empty() && !delimited {
+        return Err("'--column' needs '--csv' or '--tsv'".to_string());
+    }
+    let jsonl = options.format == Format::Jsonl;
+    if !options.fields.is_empty() && !jsonl {
+        return Err("'--field' needs '--jsonl'".to_string());
+    }
+    if jsonl && options.fields.is_empty() {
+        return Err("'--jsonl' needs at least one '--field'".to_string());
+    }
+    let npy = options.format == Format::Npy;
+    if options.per_column && !npy {
+        return Err("'--per-column' needs '--format npy'".to_string());
+    }
+    if (stats.covariance || stats.correlation) && !(delimited || jsonl || options.per_column) {
+        return Err("'--covariance' and '--correlation' need '--csv', '--tsv', '--jsonl' or '--per-column'".to_string());
+    }
+    if !simd {
+        options.separators = options.separators.scalar();
+    }
+    if inputs.is_empty() {
+        inputs.push(PathBuf::from("-"));
+    }
+    if stats.is_empty() {
+        stats = Stats::all();
+    }
+    // The OpenMetrics summary of the values has their sum, which comes with the moments.
+    if output == Output::OpenMetrics && !stats.moments() {
+        stats.mean = true;
+    }
+
+    Ok(Command::Run(Box::new(Args { inputs, stats, options, output })))
+}
@@ -0,0 +1,805 @@
+mod binary;
+mod buffer;
+mod compression;
+mod csv;
+mod error;
+mod float;
+mod jsonl;
+mod npy;
+mod scan;
+mod select;
+mod sketch;
+mod source;
+mod summary;
+#[cfg(test)]
+mod testing;
+mod tokenize;
+
+pub use binary::Element;
+pub use compression::Codec;
+pub use csv::Column;
+pub use error::{Error, ErrorKind, Location};
+pub use scan::scan;
+pub use select::{quantiles, Histogram, Interpolation, Quantile};
+pub use sketch::Kll;
+pub use source::{Input, Mapped, Source};
+pub use summary::{summarize, summarize_with, ColumnReport, Report, Stats};
+pub use tokenize::{for_each_token, for_each_token_in, Separators};
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+
+/// The result of reading or summarizing an input.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// What to do with a token that is not a usable number.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum OnError {
+    /// Stop with an error.
+    #[default]
+    Fail,
+    /// Leave the token out of the statistics.
+    Skip,
+    /// Skip, passing a warning to [`ReadOptions::on_warning`] on the first pass.
+    Warn,
+}
+
+/// Takes a warning about every value that [`OnError::Warn`] skips, such as to
+/// print it. Warnings come in input order, also from inputs scanned on
+/// several threads.
+#[derive(Clone)]
+pub struct OnWarning(Arc<dyn Fn(&Error) + Send + Sync>);
+
+impl OnWarning {
+    /// Passes every warning to `f`.
+    pub fn new(f: impl Fn(&Error) + Send + Sync + 'static) -> Self {
+        OnWarning(Arc::new(f))
+    }
+
+    fn call(&self, warning: &Error) {
+        (self.0)(warning)
+    }
+}
+
+impl fmt::Debug for OnWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OnWarning").finish_non_exhaustive()
+    }
+}
+
+/// What to do with NaN and ±inf.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum NonFinite {
+    /// Take them like any other value.
+    Include,
+    /// Leave them out of the statistics, silently.
+    Exclude,
+    /// Handle them like unparsable tokens, according to [`OnError`].
+    #[default]
+    Error,
+}
+
+/// Whether inputs are decompressed before their values are read.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Decompress {
+    /// When they start with the magic bytes of a known [`Codec`].
+    #[default]
+    Detect,
+    /// Never; the input is read as it is.
+    Never,
+    /// Always, with this codec.
+    Always(Codec),
+}
+
+/// How values are stored in the input.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum Format {
+    /// Numbers written out as text, between separators.
+    #[default]
+    Text,
+    /// A packed array of binary values, with no header.
+    Binary(Element),
+    /// A NumPy array file, which says in its header how its values are stored.
+    Npy,
+    /// Records of fields split by `delimiter`, such as CSV or TSV.
+    Delimited {
+        /// The byte between fields, such as `b','`.
+        delimiter: u8,
+    },
+    /// One JSON object per line, each giving a value of every field in
+    /// [`ReadOptions::fields`].
+    Jsonl,
+}
+
+/// How values are read out of a [`Source`].
+#[derive(Clone, Debug)]
+pub struct ReadOptions {
+    /// How values are stored.
+    pub format: Format,
+    /// What splits text values apart.
+    pub separators: Separators,
+    /// What to do with tokens that are not numbers.
+    pub on_error: OnError,
+    /// Where the warnings of [`OnError::Warn`] go; `None` drops them.
+    pub on_warning: Option<OnWarning>,
+    /// What to do with NaN and ±inf.
+    pub non_finite: NonFinite,
+    /// Give every column of a [`Format::Npy`] array its own statistics instead
+    /// of taking all values together. Other formats ignore it: delimited and
+    /// JSON Lines input always has columns, and the rest has none.
+    pub per_column: bool,
+    /// Fields of delimited records to read, each as a column of its own.
+    pub columns: Vec<Column>,
+    /// Whether the first record of a delimited file names the fields; `None`
+    /// guesses so when some field of it is not a number where the second
+    /// record holds one.
+    pub header: Option<bool>,
+    /// Dotted paths to the fields of JSON Lines objects to read, each as a column of its own.
+    pub fields: Vec<String>,
+    /// Whether to decompress the input first.
+    pub decompress: Decompress,
+    /// Map regular files into memory instead of reading them through a buffer.
+    pub mmap: bool,
+    /// Threads that scan a mapped file; 0 means one per core.
+    pub threads: usize,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        ReadOptions {
+            format: Format::default(),
+            separators: Separators::default(),
+            on_error: OnError::default(),
+            on_warning: None,
+            non_finite: NonFinite::default(),
+            per_column: false,
+            columns: Vec::new(),
+            header: None,
+            fields: Vec::new(),
+            decompress: Decompress::default(),
+            mmap: true,
+            threads: 0,
+        }
+    }
+}
+
+/// Values that were left out of the statistics, by reason.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Skipped {
+    /// Tokens that are not numbers at all.
+    pub invalid: u64,
+    /// NaN values.
+    pub nan: u64,
+    /// Infinite values.
+    pub infinite: u64,
+}
+
+impl Skipped {
+    /// All values that were left out.
+    pub fn total(&self) -> u64 {
+        self.invalid + self.nan + self.infinite
+    }
+
+    fn merge(&mut self, other: Skipped) {
+        self.invalid += other.invalid;
+        self.nan += other.nan;
+        self.infinite += other.infinite;
+    }
+
+    /// `value` is `None` for tokens that are not numbers at all.
+    fn count(&mut self, value: Option<f64>) {
+        match value {
+            None => self.invalid += 1,
+            Some(x) if x.is_nan() => self.nan += 1,
+            Some(_) => self.infinite += 1,
+        }
+    }
+}
+
+/// A statistic that sees every value exactly once, in file order.
+///
+/// Several accumulators can be combined into a tuple, so that all of them are
+/// computed during the same [`scan`]; [`Option`] switches one off, and
+/// [`Columns`] keeps one per column of the input. The built-in statistics,
+/// such as [`MinMax`], [`Moments`] and [`Kll`], are accumulators too.
+///
+/// [`scan`] gives every thread a clone of the accumulator it is handed and
+/// merges the clones back in input order, so a statistic of one's own is
+/// computed in parallel as long as [`merge`](Self::merge) gives the same
+/// result as pushing the values of `other` one by one:
+///
+/// ```no_run
+/// use bigfilestat::{scan, Accumulator, Len, ReadOptions, Source};
+///
+/// /// Values above a limit.
+/// #[derive(Clone)]
+/// struct Above {
+///     limit: f64,
+///     count: u64,
+/// }
+///
+/// impl Accumulator for Above {
+///     type Output = u64;
+///
+///     fn push(&mut self, x: f64) {
+///         self.count += u64::from(x > self.limit);
+///     }
+///
+///     fn merge(&mut self, other: Self) {
+///         self.count += other.count;
+///     }
+///
+///     fn finish(self) -> u64 {
+///         self.count
+///     }
+/// }
+///
+/// let mut source = Source::new("latencies.txt", ReadOptions::default());
+/// let ((above, len), _skipped) = scan(&mut source, (Above { limit: 0.25, count: 0 }, Len::default()))?;
+/// println!("{above} of {len} requests took longer than 250 ms");
+/// # Ok::<(), bigfilestat::Error>(())
+/// ```
+pub trait Accumulator {
+    /// What the statistic comes out as.
+    type Output;
+
+    /// Takes the next value.
+    fn push(&mut self, x: f64);
+    /// Takes a value of the given column of the input. Only [`Columns`] tells
+    /// columns apart; everything else takes the value like [`push`](Self::push).
+    fn push_column(&mut self, column: usize, x: f64) {
+        let _ = column;
+        self.push(x);
+    }
+    /// Marks the end of a row: the values pushed since the last row end came
+    /// from the same record. Only inputs with records have rows.
+    fn end_row(&mut self) {}
+    /// Takes in `other`, which has seen the values that come right after the
+    /// ones this accumulator has seen.
+    fn merge(&mut self, other: Self);
+    /// The statistic of all values seen.
+    fn finish(self) -> Self::Output;
+}
+
+macro_rules! impl_accumulator_for_tuple {
+    ($($acc:ident $idx:tt),+) => {
+        impl<$($acc: Accumulator),+> Accumulator for ($($acc,)+) {
+            type Output = ($($acc::Output,)+);
+
+            fn push(&mut self, x: f64) {
+                $(self.$idx.push(x);)+
+            }
+
+            fn push_column(&mut self, column: usize, x: f64) {
+                $(self.$idx.push_column(column, x);)+
+            }
+
+            fn end_row(&mut self) {
+                $(self.$idx.end_row();)+
+            }
+
+            fn merge(&mut self, other: Self) {
+                $(self.$idx.merge(other.$idx);)+
+            }
+
+            fn finish(self) -> Self::Output {
+                ($(self.$idx.finish(),)+)
+            }
+        }
+    };
+}
+
+impl_accumulator_for_tuple!(A 0);
+impl_accumulator_for_tuple!(A 0, B 1);
+impl_accumulator_for_tuple!(A 0, B 1, C 2);
+impl_accumulator_for_tuple!(A 0, B 1, C 2, D 3);
+impl_accumulator_for_tuple!(A 0, B 1, C 2, D 3, E 4);
+impl_accumulator_for_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+impl_accumulator_for_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_accumulator_for_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+/// No statistic at all.
+impl Accumulator for () {
+    type Output = ();
+
+    fn push(&mut self, _: f64) {}
+
+    fn merge(&mut self, _: Self) {}
+
+    fn finish(self) {}
+}
+
+/// Lets a statistic be switched off without changing the shape of the tuple it lives in.
+impl<A: Accumulator> Accumulator for Option<A> {
+    type Output = Option<A::Output>;
+
+    fn push(&mut self, x: f64) {
+        if let Some(acc) = self {
+            acc.push(x);
+        }
+    }
+
+    fn push_column(&mut self, column: usize, x: f64) {
+        if let Some(acc) = self {
+            acc.push_column(column, x);
+        }
+    }
+
+    fn end_row(&mut self) {
+        if let Some(acc) = self {
+            acc.end_row();
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        if let (Some(acc), Some(other)) = (self, other) {
+            acc.merge(other);
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        self.map(A::finish)
+    }
+}
+
+/// A separate accumulator for every column of the input, each a clone of the
+/// one given for the first column. Columns are added as their values show up.
+#[derive(Clone, Debug)]
+pub struct Columns<A> {
+    columns: Vec<A>,
+    /// What a column starts from.
+    empty: A,
+}
+
+impl<A: Accumulator + Clone> Columns<A> {
+    /// Starts every column off with a copy of `empty`.
+    pub fn new(empty: A) -> Self {
+        Columns { columns: vec![empty.clone()], empty }
+    }
+
+    /// Starts the first columns off with `columns` instead of copies of `empty`.
+    pub fn with_columns(columns: Vec<A>, empty: A) -> Self {
+        Columns { columns, empty }
+    }
+
+    fn column(&mut self, column: usize) -> &mut A {
+        if column >= self.columns.len() {
+            self.columns.resize(column + 1, self.empty.clone());
+        }
+        &mut self.columns[column]
+    }
+}
+
+impl<A: Accumulator + Clone> Accumulator for Columns<A> {
+    type Output = Vec<A::Output>;
+
+    fn push(&mut self, x: f64) {
+        self.push_column(0, x);
+    }
+
+    fn push_column(&mut self, column: usize, x: f64) {
+        self.column(column).push(x);
+    }
+
+    fn end_row(&mut self) {
+        self.columns.iter_mut().for_each(A::end_row);
+    }
+
+    fn merge(&mut self, other: Self) {
+        for (i, other) in other.columns.into_iter().enumerate() {
+            self.column(i).merge(other);
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        self.columns.into_iter().map(A::finish).collect()
+    }
+}
+
+/// The number of values.
+#[derive(Clone, Default, Debug)]
+pub struct Len(usize);
+
+impl Accumulator for Len {
+    type Output = usize;
+
+    fn push(&mut self, _: f64) {
+        self.0 += 1;
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.0 += other.0;
+    }
+
+    fn finish(self) -> usize {
+        self.0
+    }
+}
+
+/// The smallest and the largest value, or `None` without values.
+#[derive(Clone, Default, Debug)]
+pub struct MinMax {
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl Accumulator for MinMax {
+    type Output = Option<(f64, f64)>;
+
+    fn push(&mut self, x: f64) {
+        let _ = self.min.insert(x.min(self.min.unwrap_or(x)));
+        let _ = self.max.insert(x.max(self.max.unwrap_or(x)));
+    }
+
+    fn merge(&mut self, other: Self) {
+        if let Some((min, max)) = other.finish() {
+            self.push(min);
+            self.push(max);
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        self.min.zip(self.max)
+    }
+}
+
+/// Count, sum and central moments up to the fourth, in one numerically stable pass.
+///
+/// The mean and the sums of powers of deviations are updated incrementally
+/// (Welford, extended to higher moments by Pébay), so no `sum of squares`
+/// ever has to be cancelled against the square of a sum. The plain sum is
+/// kept with Neumaier compensation.
+#[derive(Clone, Default, Debug)]
+pub struct Moments {
+    len: u64,
+    sum: f64,
+    compensation: f64,
+    mean: f64,
+    m2: f64,
+    m3: f64,
+    m4: f64,
+}
+
+impl Moments {
+    fn add_to_sum(&mut self, x: f64) {
+        let t = self.sum + x;
+        self.compensation += if self.sum.abs() >= x.abs() { (self.sum - t) + x } else { (x - t) + self.sum };
+        self.sum = t;
+    }
+
+    /// The sum of all values.
+    pub fn sum(&self) -> f64 {
+        self.sum + self.compensation
+    }
+
+    /// Taken from the compensated sum, which drifts less than the running mean.
+    pub fn mean(&self) -> f64 {
+        self.sum() / self.len as f64
+    }
+
+    /// Population variance, dividing by `len`.
+    pub fn variance(&self) -> f64 {
+        self.m2 / self.len as f64
+    }
+
+    /// Sample variance, dividing by `len - 1`.
+    pub fn sample_variance(&self) -> f64 {
+        self.m2 / (self.len as f64 - 1.0)
+    }
+
+    /// Population standard deviation.
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    /// Skewness: 0 for a symmetric distribution.
+    pub fn skewness(&self) -> f64 {
+        (self.len as f64).sqrt() * self.m3 / self.m2.powf(1.5)
+    }
+
+    /// Excess kurtosis: 0 for a normal distribution.
+    pub fn kurtosis(&self) -> f64 {
+        self.len as f64 * self.m4 / (self.m2 * self.m2) - 3.0
+    }
+}
+
+impl Accumulator for Moments {
+    type Output = Moments;
+
+    fn push(&mut self, x: f64) {
+        self.add_to_sum(x);
+
+        let n1 = self.len as f64;
+        self.len += 1;
+        let n = self.len as f64;
+        let delta = x - self.mean;
+        let delta_n = delta / n;
+        let delta_n2 = delta_n * delta_n;
+        let term = delta * delta_n * n1;
+
+        self.mean += delta_n;
+        self.m4 += term * delta_n2 * (n * n - 3.0 * n + 3.0) + 6.0 * delta_n2 * self.m2 - 4.0 * delta_n * self.m3;
+        self.m3 += term * delta_n * (n - 2.0) - 3.0 * delta_n * self.m2;
+        self.m2 += term;
+    }
+
+    fn merge(&mut self, other: Self) {
+        if other.len == 0 {
+            return;
+        }
+        if self.len == 0 {
+            *self = other;
+            return;
+        }
+
+        self.add_to_sum(other.sum);
+        self.compensation += other.compensation;
+
+        let (na, nb) = (self.len as f64, other.len as f64);
+        let n = na + nb;
+        let delta = other.mean - self.mean;
+        let (delta2, delta3, delta4) = (delta * delta, delta * delta * delta, delta * delta * delta * delta);
+        let (m2a, m3a) = (self.m2, self.m3);
+
+        self.len += other.len;
+        self.mean += delta * nb / n;
+        self.m4 += other.m4
+            + delta4 * na * nb * (na * na - na * nb + nb * nb) / (n * n * n)
+            + 6.0 * delta2 * (na * na * other.m2 + nb * nb * m2a) / (n * n)
+            + 4.0 * delta * (na * other.m3 - nb * m3a) / n;
+        self.m3 += other.m3
+            + delta3 * na * nb * (na - nb) / (n * n)
+            + 3.0 * delta * (na * other.m2 - nb * m2a) / n;
+        self.m2 += other.m2 + delta2 * na * nb / n;
+    }
+
+    fn finish(self) -> Moments {
+        self
+    }
+}
+
+/// Co-moments of every pair of columns, for their covariance and correlation.
+///
+/// Each pair only takes the rows that have a value in both of its columns, so
+/// a skipped value drops out of the pairs of its own column alone.
+#[derive(Clone, Default, Debug)]
+pub struct Comoments {
+    /// `pairs[j][i]` is the pair of columns `i <= j`.
+    pairs: Vec<Vec<Pair>>,
+    row: Vec<Option<f64>>,
+}
+
+/// Means, sums of squared deviations and the co-moment of two columns.
+#[derive(Clone, Copy, Default, Debug)]
+struct Pair {
+    len: u64,
+    mean_x: f64,
+    mean_y: f64,
+    m2_x: f64,
+    m2_y: f64,
+    c: f64,
+}
+
+impl Pair {
+    fn push(&mut self, x: f64, y: f64) {
+        self.len += 1;
+        let n = self.len as f64;
+        let dx = x - self.mean_x;
+        let dy = y - self.mean_y;
+        self.mean_x += dx / n;
+        self.mean_y += dy / n;
+        self.m2_x += dx * (x - self.mean_x);
+        self.m2_y += dy * (y - self.mean_y);
+        self.c += dx * (y - self.mean_y);
+    }
+
+    fn merge(&mut self, other: Pair) {
+        if other.len == 0 {
+            return;
+        }
+        if self.len == 0 {
+            *self = other;
+            return;
+        }
+        let (na, nb) = (self.len as f64, other.len as f64);
+        let n = na + nb;
+        let dx = other.mean_x - self.mean_x;
+        let dy = other.mean_y - self.mean_y;
+        self.len += other.len;
+        self.mean_x += dx * nb / n;
+        self.mean_y += dy * nb / n;
+        self.m2_x += other.m2_x + dx * dx * na * nb / n;
+        self.m2_y += other.m2_y + dy * dy * na * nb / n;
+        self.c += other.c + dx * dy * na * nb / n;
+    }
+}
+
+impl Comoments {
+    fn pair(&self, i: usize, j: usize) -> Pair {
+        let (i, j) = (i.min(j), i.max(j));
+        self.pairs.get(j).and_then(|pairs| pairs.get(i)).copied().unwrap_or_default()
+    }
+
+    /// Sample covariance of columns `i` and `j`, dividing by `len - 1`; NaN
+    /// for fewer than two rows with both.
+    pub fn covariance(&self, i: usize, j: usize) -> f64 {
+        let pair = self.pair(i, j);
+        if pair.len < 2 {
+            return f64::NAN;
+        }
+        pair.c / (pair.len as f64 - 1.0)
+    }
+
+    /// Pearson correlation coefficient of columns `i` and `j`.
+    pub fn correlation(&self, i: usize, j: usize) -> f64 {
+        let pair = self.pair(i, j);
+        pair.c / (pair.m2_x * pair.m2_y).sqrt()
+    }
+}
+
+impl Accumulator for Comoments {
+    type Output = Comoments;
+
+    /// Values that come without a column cannot be paired with anything.
+    fn push(&mut self, _: f64) {}
+
+    fn push_column(&mut self, column: usize, x: f64) {
+        if column >= self.row.len() {
+            self.row.resize(column + 1, None);
+        }
+        self.row[column] = Some(x);
+    }
+
+    fn end_row(&mut self) {
+        while self.pairs.len() < self.row.len() {
+            self.pairs.push(vec![Pair::default(); self.pairs.len() + 1]);
+        }
+        for (j, y) in self.row.iter().enumerate() {
+            let Some(y) = *y else { continue };
+            for (i, x) in self.row[..=j].iter().enumerate() {
+                if let Some(x) = *x {
+                    self.pairs[j][i].push(x, y);
+                }
+            }
+        }
+        self.row.fill(None);
+    }
+
+    fn merge(&mut self, other: Self) {
+        while self.pairs.len() < other.pairs.len() {
+            self.pairs.push(vec![Pair::default(); self.pairs.len() + 1]);
+        }
+        for (pairs, other) in self.pairs.iter_mut().zip(other.pairs) {
+            for (pair, other) in pairs.iter_mut().zip(other) {
+                pair.merge(other);
+            }
+        }
+    }
+
+    fn finish(self) -> Comoments {
+        self
+    }
+}
+
+/// The first and the last `len` values of the input.
+#[derive(Clone, Debug)]
+pub struct Tails {
+    len: usize,
+    left: Vec<f64>,
+    right: VecDeque<f64>,
+}
+
+impl Tails {
+    /// Keeps `len` values at either end.
+    pub fn new(len: usize) -> Self {
+        Tails { len, left: Vec::new(), right: VecDeque::new() }
+    }
+}
+
+impl Accumulator for Tails {
+    type Output = (Vec<f64>, VecDeque<f64>);
+
+    fn push(&mut self, x: f64) {
+        if self.left.len() < self.len {
+            self.left.push(x);
+        }
+
+        self.right.push_back(x);
+        if self.right.len() > self.len {
+            self.right.pop_front();
+        }
+    }
+
+    /// The left tail is filled up from `other` only if this one is short, and
+    /// the right tail is what is left of both after the last `len` values.
+    fn merge(&mut self, other: Self) {
+        let missing = self.len - self.left.len();
+        self.left.extend(other.left.into_iter().take(missing));
+
+        self.right.extend(other.right);
+        let excess = self.right.len().saturating_sub(self.len);
+        self.right.drain(..excess);
+    }
+
+    fn finish(self) -> Self::Output {
+        (self.left, self.right)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Accumulator, Moments};
+    use crate::file_stat::testing::Rng;
+
+    fn push_all<A: Accumulator>(mut acc: A, values: &[f64]) -> A {
+        values.iter().for_each(|&x| acc.push(x));
+        acc
+    }
+
+    /// Copies of `empty` fed `values` cut at `splits`, merged back in order.
+    fn merged<A: Accumulator + Clone>(empty: &A, values: &[f64], splits: &[usize]) -> A {
+        let mut bounds = vec![0];
+        bounds.extend(splits);
+        bounds.push(values.len());
+        bounds.windows(2)
+            .map(|pair| push_all(empty.clone(), &values[pair[0]..pair[1]]))
+            .reduce(|mut acc, chunk| {
+                acc.merge(chunk);
+                acc
+            })
+            .unwrap()
+    }
+
+    fn assert_close(a: f64, b: f64, what: &str) {
+        assert!((a - b).abs() <= 1e-9 * a.abs().max(b.abs()).max(1.0), "{what}: {a} != {b}");
+    }
+
+    fn assert_same_moments(a: &Moments, b: &Moments) {
+        assert_eq!(a.len, b.len);
+        assert_close(a.sum(), b.sum(), "sum");
+        assert_close(a.mean(), b.mean(), "mean");
+        assert_close(a.variance(), b.variance(), "variance");
+        assert_close(a.skewness(), b.skewness(), "skewness");
+        assert_close(a.kurtosis(), b.kurtosis(), "kurtosis");
+    }
+
+    #[test]
+    fn merged_moments_match_a_sequential_push() {
+        let values: Vec<f64> = Rng(5).values(10_000).iter().map(|x| x + 1e6).collect();
+        let sequential = push_all(Moments::default(), &values);
+        for splits in [&[][..], &[0], &[1], &[9_999], &[10_000], &[5_000], &[3, 4, 5, 2_000, 9_000], &[0, 0, 7_000, 7_000]] {
+            assert_same_moments(&merged(&Moments::default(), &values, splits), &sequential);
+        }
+        let mut rng = Rng(6);
+        let mut splits: Vec<usize> = (0..64).map(|_| rng.below(10_001) as usize).collect();
+        splits.sort();
+        assert_same_moments(&merged(&Moments::default(), &values, &splits), &sequential);
+    }
+
+    #[test]
+    fn moments_match_two_passes() {
+        let values = Rng(7).values(1_000);
+        let moments = push_all(Moments::default(), &values);
+        let len = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / len;
+        let central = |power: i32| values.iter().map(|x| (x - mean).powi(power)).sum::<f64>() / len;
+        assert_close(moments.mean(), mean, "mean");
+        assert_close(moments.variance(), central(2), "variance");
+        assert_close(moments.skewness(), central(3) / central(2).powf(1.5), "skewness");
+        assert_close(moments.kurtosis(), central(4) / (central(2) * central(2)) - 3.0, "kurtosis");
+    }
+
+    #[test]
+    fn merging_empty_moments_changes_nothing() {
+        let values = [1.0, 2.0, 4.0];
+        let mut moments = push_all(Moments::default(), &values);
+        moments.merge(Moments::default());
+        assert_same_moments(&moments, &push_all(Moments::default(), &values));
+        let mut empty = Moments::default();
+        empty.merge(push_all(Moments::default(), &values));
+        assert_same_moments(&empty, &moments);
+    }
+}
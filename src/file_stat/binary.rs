@@ -0,0 +1,136 @@
+use std::fmt;
+use std::io::Read;
+
+use super::{buffer, ErrorKind};
+
+/// The type of the values in raw binary input, such as `f64le`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Element {
+    kind: Kind,
+    big_endian: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Kind {
+    F32,
+    F64,
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+const KINDS: [(&str, Kind); 10] = [
+    ("f32", Kind::F32),
+    ("f64", Kind::F64),
+    ("i8", Kind::I8),
+    ("i16", Kind::I16),
+    ("i32", Kind::I32),
+    ("i64", Kind::I64),
+    ("u8", Kind::U8),
+    ("u16", Kind::U16),
+    ("u32", Kind::U32),
+    ("u64", Kind::U64),
+];
+
+impl Element {
+    /// Parses names like `f64le` or `i32be`. Single bytes take no byte order: `i8`, `u8`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        KINDS.iter().find_map(|&(prefix, kind)| {
+            let order = name.strip_prefix(prefix)?;
+            let big_endian = match order {
+                "le" => false,
+                "be" => true,
+                "" if matches!(kind, Kind::I8 | Kind::U8) => false,
+                _ => return None,
+            };
+            Some(Element { kind, big_endian })
+        })
+    }
+
+    /// Bytes per value.
+    pub fn size(self) -> usize {
+        match self.kind {
+            Kind::I8 | Kind::U8 => 1,
+            Kind::I16 | Kind::U16 => 2,
+            Kind::F32 | Kind::I32 | Kind::U32 => 4,
+            Kind::F64 | Kind::I64 | Kind::U64 => 8,
+        }
+    }
+
+    /// Calls `action` with every value of `data` and its byte offset. `data`
+    /// must hold a whole number of elements; `offset` is where it starts in
+    /// the input.
+    ///
+    /// 64-bit integers above 2^53 are rounded to the nearest `f64`.
+    pub fn for_each_in<F>(self, data: &[u8], offset: u64, mut action: F) -> Result<(), ErrorKind>
+    where
+        F: FnMut(f64, u64) -> Result<(), ErrorKind>,
+    {
+        debug_assert_eq!(data.len() % self.size(), 0);
+        macro_rules! decode {
+            ($t:ty) => {{
+                const SIZE: usize = std::mem::size_of::<$t>();
+                for (i, bytes) in data.chunks_exact(SIZE).enumerate() {
+                    let bytes: [u8; SIZE] = bytes.try_into().unwrap();
+                    let x = if self.big_endian { <$t>::from_be_bytes(bytes) } else { <$t>::from_le_bytes(bytes) };
+                    action(x as f64, offset + (i * SIZE) as u64)?;
+                }
+            }};
+        }
+        match self.kind {
+            Kind::F32 => decode!(f32),
+            Kind::F64 => decode!(f64),
+            Kind::I8 => decode!(i8),
+            Kind::I16 => decode!(i16),
+            Kind::I32 => decode!(i32),
+            Kind::I64 => decode!(i64),
+            Kind::U8 => decode!(u8),
+            Kind::U16 => decode!(u16),
+            Kind::U32 => decode!(u32),
+            Kind::U64 => decode!(u64),
+        }
+        Ok(())
+    }
+
+    /// Checks that an input of `len` bytes holds a whole number of elements.
+    pub fn check_len(self, len: u64) -> Result<(), ErrorKind> {
+        match (len % self.size() as u64) as usize {
+            0 => Ok(()),
+            trailing => Err(ErrorKind::TrailingBytes { len, element: self, trailing }),
+        }
+    }
+
+    /// Calls `action` with every value of `reader` and its byte offset. Bytes
+    /// left over at the end that do not make up a whole element are an error.
+    pub fn for_each<R, F>(self, reader: R, mut action: F) -> Result<(), ErrorKind>
+    where
+        R: Read,
+        F: FnMut(f64, u64) -> Result<(), ErrorKind>,
+    {
+        buffer::read_records(reader, |data, offset, eof| {
+            let whole = data.len() - data.len() % self.size();
+            self.for_each_in(&data[..whole], offset, &mut action)?;
+            if eof {
+                self.check_len(offset + data.len() as u64)?;
+            }
+            Ok(whole)
+        })
+    }
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, _) = KINDS.iter().find(|(_, kind)| *kind == self.kind).unwrap();
+        let order = match (self.size(), self.big_endian) {
+            (1, _) => "",
+            (_, false) => "le",
+            (_, true) => "be",
+        };
+        write!(f, "{name}{order}")
+    }
+}
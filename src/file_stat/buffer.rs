@@ -0,0 +1,113 @@
+use std::io::{self, Read};
+
+const BUF_SIZE: usize = 64 * 1024;
+
+/// Reads `reader` through a buffer and hands what it holds to `split`, with
+/// where it starts in the input and whether the input ends there.
+///
+/// `split` takes in every record that is complete and returns where the first
+/// incomplete one starts; that rest is kept for the next read, which may
+/// finish it. A record that does not fit into the buffer grows it, so records
+/// of any length come out whole. At the end of the input, `split` is called
+/// once more with `eof` set, even if nothing is left.
+pub fn read_records<R, E, F>(mut reader: R, mut split: F) -> Result<(), E>
+where
+    R: Read,
+    E: From<io::Error>,
+    F: FnMut(&[u8], u64, bool) -> Result<usize, E>,
+{
+    let mut buf = vec![0u8; BUF_SIZE];
+    let mut len = 0;
+    // Offset of `buf[0]` in the input.
+    let mut offset = 0u64;
+
+    loop {
+        let n = match reader.read(&mut buf[len..]) {
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        let eof = n == 0;
+        len += n;
+
+        let keep = split(&buf[..len], offset, eof)?;
+
+        if eof {
+            return Ok(());
+        }
+        buf.copy_within(keep..len, 0);
+        len -= keep;
+        offset += keep as u64;
+        if len == buf.len() {
+            buf.resize(2 * len, 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::file_stat::testing::{Rng, Trickle};
+    use crate::file_stat::{for_each_token, for_each_token_in, Element, ErrorKind, Separators};
+
+    fn tokens_read(reader: impl std::io::Read) -> Vec<(Vec<u8>, u64)> {
+        let mut tokens = Vec::new();
+        for_each_token(reader, &Separators::default(), |token, offset| {
+            tokens.push((token.to_vec(), offset));
+            Ok::<_, std::io::Error>(())
+        })
+        .unwrap();
+        tokens
+    }
+
+    fn tokens_in(data: &[u8]) -> Vec<(Vec<u8>, u64)> {
+        let mut tokens = Vec::new();
+        for_each_token_in(data, 0, &Separators::default(), |token, offset| {
+            tokens.push((token.to_vec(), offset));
+            Ok::<_, ()>(())
+        })
+        .unwrap();
+        tokens
+    }
+
+    #[test]
+    fn tokens_come_out_whole_however_the_input_is_read() {
+        let mut rng = Rng(13);
+        let data: Vec<u8> = rng.values(2_000).iter().flat_map(|x| format!("{x:?} \n ").into_bytes()).collect();
+        let expected = tokens_in(&data);
+        assert_eq!(expected.len(), 2_000);
+        assert_eq!(tokens_read(&data[..]), expected);
+        for max in [1, 3, 64] {
+            assert_eq!(tokens_read(Trickle::new(&data, max)), expected, "reads of up to {max} bytes");
+        }
+    }
+
+    #[test]
+    fn tokens_longer_than_the_buffer_grow_it() {
+        let mut data = b"1 ".to_vec();
+        data.extend(b"2".repeat(3 * super::BUF_SIZE));
+        data.extend(b"\n3");
+        let expected = tokens_in(&data);
+        assert_eq!(expected.len(), 3);
+        assert_eq!(tokens_read(&data[..]), expected);
+        assert_eq!(tokens_read(Trickle::new(&data, 4_096)), expected);
+    }
+
+    #[test]
+    fn binary_values_come_out_whole_however_the_input_is_read() {
+        let element = Element::from_name("f32be").unwrap();
+        let values: Vec<f32> = (0..1_000).map(|i| i as f32 * 0.5).collect();
+        let data: Vec<u8> = values.iter().flat_map(|x| x.to_be_bytes()).collect();
+        for max in [1, 3, 5, 4_096] {
+            let mut read = Vec::new();
+            element.for_each(Trickle::new(&data, max), |x, offset| {
+                read.push((x as f32, offset));
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(read, values.iter().enumerate().map(|(i, &x)| (x, 4 * i as u64)).collect::<Vec<_>>());
+        }
+
+        let err = element.for_each(Trickle::new(&data[..3_998], 3), |_, _| Ok(())).unwrap_err();
+        assert!(matches!(err, ErrorKind::TrailingBytes { len: 3_998, trailing: 2, .. }), "{err}");
+    }
+}
@@ -0,0 +1,172 @@
+use std::io::{self, Cursor, Read};
+
+use flate2::read::MultiGzDecoder;
+use xz2::read::XzDecoder;
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+/// A compression format that inputs can be decompressed from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Codec {
+    /// gzip, as written by `gzip` and `pigz`.
+    Gzip,
+    /// Zstandard.
+    Zstd,
+    /// xz, which is LZMA2 in a container.
+    Xz,
+}
+
+const MAGIC: [(&[u8], Codec); 3] = [
+    (b"\x1f\x8b", Codec::Gzip),
+    (b"\x28\xb5\x2f\xfd", Codec::Zstd),
+    (b"\xfd7zXZ\x00", Codec::Xz),
+];
+
+/// Enough bytes to tell every codec by.
+const MAGIC_LEN: usize = 6;
+
+impl Codec {
+    /// The codec called `name`: `gzip`, `zstd` or `xz`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "gzip" => Some(Codec::Gzip),
+            "zstd" => Some(Codec::Zstd),
+            "xz" => Some(Codec::Xz),
+            _ => None,
+        }
+    }
+
+    /// The codec whose magic bytes `data` starts with.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        MAGIC.iter().find(|(magic, _)| data.starts_with(magic)).map(|&(_, codec)| codec)
+    }
+
+    /// Wraps `reader` so that it gives the decompressed data. Concatenated
+    /// streams are decompressed one after another, as the command-line tools do.
+    pub fn decoder<'a>(self, reader: impl Read + 'a) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Codec::Gzip => Box::new(MultiGzDecoder::new(reader)),
+            Codec::Zstd => Box::new(ZstdDecoder::new(reader)?),
+            Codec::Xz => Box::new(XzDecoder::new_multi_decoder(reader)),
+        })
+    }
+}
+
+/// Reads the first bytes of `reader` to tell its codec by, and returns the
+/// codec with a reader that still gives those bytes.
+pub fn sniff<'a>(mut reader: Box<dyn Read + 'a>) -> io::Result<(Option<Codec>, Box<dyn Read + 'a>)> {
+    let mut magic = [0u8; MAGIC_LEN];
+    let mut len = 0;
+    while len < MAGIC_LEN {
+        match reader.read(&mut magic[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    let codec = Codec::detect(&magic[..len]);
+    Ok((codec, Box::new(Cursor::new(magic).take(len as u64).chain(reader))))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::{sniff, Codec, MAGIC_LEN};
+    use crate::file_stat::testing::{TestFile, Trickle};
+    use crate::file_stat::{summarize, Decompress, Input, Interpolation, ReadOptions, Stats};
+
+    fn compress(codec: Codec, data: &[u8]) -> Vec<u8> {
+        match codec {
+            Codec::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+            Codec::Zstd => zstd::encode_all(data, 3).unwrap(),
+            Codec::Xz => {
+                let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+        }
+    }
+
+    fn read_all(mut reader: impl Read) -> Vec<u8> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        data
+    }
+
+    /// Everything one pass over `options`'s view of a file of `contents` gives.
+    fn first_pass(contents: &[u8], options: ReadOptions) -> Vec<u8> {
+        let file = TestFile::new(contents);
+        match file.source(options).open().unwrap() {
+            Input::Mapped(map) => map.to_vec(),
+            Input::Stream(reader) => read_all(reader),
+        }
+    }
+
+    #[test]
+    fn sniffing_keeps_short_inputs() {
+        let gzip = compress(Codec::Gzip, b"1\n");
+        let xz = compress(Codec::Xz, b"1\n");
+        for data in [&b""[..], b"1", b"1\n2\n", b"\x1f", &gzip[..2], &gzip[..3], &xz[..MAGIC_LEN - 1], &xz[..MAGIC_LEN]] {
+            let expected = Codec::detect(data);
+            for max in [1, 2, 8] {
+                let (codec, reader) = sniff(Box::new(Trickle::new(data, max))).unwrap();
+                assert_eq!(codec, expected, "{data:?}");
+                assert_eq!(read_all(reader), data, "{max} bytes at a time");
+            }
+        }
+        assert_eq!(Codec::detect(&gzip[..2]), Some(Codec::Gzip));
+        assert_eq!(Codec::detect(&xz[..MAGIC_LEN - 1]), None);
+        assert_eq!(Codec::detect(&xz[..MAGIC_LEN]), Some(Codec::Xz));
+    }
+
+    #[test]
+    fn detects_and_decompresses_every_codec() {
+        let data = b"1\n2\n3\n".repeat(1000);
+        for codec in [Codec::Gzip, Codec::Zstd, Codec::Xz] {
+            let compressed = compress(codec, &data);
+            let (detected, reader) = sniff(Box::new(&compressed[..])).unwrap();
+            assert_eq!(detected, Some(codec));
+            assert_eq!(read_all(codec.decoder(reader).unwrap()), data, "{codec:?}");
+            assert_eq!(first_pass(&compressed, ReadOptions::default()), data, "{codec:?}");
+        }
+        assert_eq!(sniff(Box::new(&data[..])).unwrap().0, None);
+    }
+
+    #[test]
+    fn decompresses_concatenated_streams() {
+        for codec in [Codec::Gzip, Codec::Zstd, Codec::Xz] {
+            let mut compressed = compress(codec, b"1\n2\n");
+            compressed.extend(compress(codec, b"3\n"));
+            assert_eq!(read_all(codec.decoder(&compressed[..]).unwrap()), b"1\n2\n3\n", "{codec:?}");
+        }
+    }
+
+    #[test]
+    fn reads_compressed_files_as_they_are_when_told_to() {
+        let compressed = compress(Codec::Gzip, b"1\n2\n");
+        let options = ReadOptions { decompress: Decompress::Never, ..ReadOptions::default() };
+        assert_eq!(first_pass(&compressed, options.clone()), compressed);
+
+        let file = TestFile::new(&compressed);
+        assert!(file.source(options).is_rewindable());
+        assert!(!file.source(ReadOptions::default()).is_rewindable());
+    }
+
+    #[test]
+    fn takes_the_median_of_compressed_files_from_the_spool() {
+        let values = "5\n1\n4\n2\n3\n10\n".repeat(100);
+        let file = TestFile::new(compress(Codec::Zstd, values.as_bytes()));
+        let mut source = file.source(ReadOptions::default());
+        let stats = Stats { median: true, ..Stats::default() };
+        let report = summarize(&mut source, &stats).unwrap();
+        assert!(source.passes() > 1);
+        let median = report.columns[0].median.unwrap();
+        assert_eq!((median.lower, median.upper), (3.0, 4.0));
+        assert_eq!(median.value(Interpolation::Midpoint), 3.5);
+    }
+}
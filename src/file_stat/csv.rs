@@ -0,0 +1,338 @@
+use std::borrow::Cow;
+use std::io::Read;
+
+use super::{buffer, ErrorKind};
+
+/// A column of a delimited file, as asked for on the command line.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Column {
+    /// 0-based position of the field in the record.
+    Index(usize),
+    /// Name of the field in the header.
+    Name(String),
+}
+
+/// Where a field is in the data of a record. Quotes are already stripped.
+#[derive(Clone, Copy)]
+struct Field {
+    start: usize,
+    end: usize,
+    /// Quoted and containing `""`, which stands for one quote.
+    escaped: bool,
+}
+
+/// One line of a delimited file, split into fields.
+pub struct Record<'a> {
+    data: &'a [u8],
+    fields: &'a [Field],
+    /// Offset of the record in the input.
+    pub offset: u64,
+}
+
+impl<'a> Record<'a> {
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn field(&self, i: usize) -> Option<Cow<'a, [u8]>> {
+        let field = self.fields.get(i)?;
+        let raw = &self.data[field.start..field.end];
+        if !field.escaped {
+            return Some(Cow::Borrowed(raw));
+        }
+        let mut unescaped = Vec::with_capacity(raw.len());
+        let mut quote = false;
+        for &b in raw {
+            if b == b'"' && quote {
+                quote = false;
+                continue;
+            }
+            quote = b == b'"';
+            unescaped.push(b);
+        }
+        Some(Cow::Owned(unescaped))
+    }
+
+    /// Offset of field `i` in the input.
+    pub fn field_offset(&self, i: usize) -> u64 {
+        self.offset + self.fields.get(i).map_or(0, |field| field.start as u64)
+    }
+
+    /// A copy that outlives the buffer the record is in.
+    pub fn to_buf(&self) -> RecordBuf {
+        let len = self.fields.last().map_or(0, |field| field.end);
+        RecordBuf { data: self.data[..len].to_vec(), fields: self.fields.to_vec(), offset: self.offset }
+    }
+}
+
+/// A record kept for later, such as a first record that may be a header.
+pub struct RecordBuf {
+    data: Vec<u8>,
+    fields: Vec<Field>,
+    offset: u64,
+}
+
+impl RecordBuf {
+    pub fn record(&self) -> Record<'_> {
+        Record { data: &self.data, fields: &self.fields, offset: self.offset }
+    }
+}
+
+/// Splits the record that starts at `data[0]` into `fields`, and returns
+/// where the next record starts, or `None` if the record does not end
+/// within `data` and more input may follow.
+fn split_record(data: &[u8], eof: bool, delimiter: u8, fields: &mut Vec<Field>) -> Option<usize> {
+    fields.clear();
+    let len = data.len();
+    let mut i = 0;
+    loop {
+        let field = if data.get(i) == Some(&b'"') {
+            let start = i + 1;
+            let mut escaped = false;
+            i = start;
+            loop {
+                match data[i..].iter().position(|&b| b == b'"') {
+                    None if eof => {
+                        i = len;
+                        break;
+                    }
+                    None => return None,
+                    Some(quote) => i += quote,
+                }
+                match data.get(i + 1) {
+                    Some(b'"') => {
+                        escaped = true;
+                        i += 2;
+                    }
+                    None if !eof => return None,
+                    _ => break,
+                }
+            }
+            let end = i;
+            // Anything between the closing quote and the delimiter is dropped.
+            while i < len && data[i] != delimiter && data[i] != b'\n' {
+                i += 1;
+            }
+            if i == len && !eof {
+                return None;
+            }
+            Field { start, end, escaped }
+        } else {
+            let start = i;
+            while i < len && data[i] != delimiter && data[i] != b'\n' {
+                i += 1;
+            }
+            let end = if i < len || eof { i } else { return None };
+            // A CRLF line break leaves its CR at the end of the last field.
+            let end = if end > start && data[end - 1] == b'\r' && data.get(i) != Some(&delimiter) { end - 1 } else { end };
+            Field { start, end, escaped: false }
+        };
+        fields.push(field);
+
+        match data.get(i) {
+            Some(&b) if b == delimiter => i += 1,
+            Some(_) => return Some(i + 1),
+            None => return Some(len),
+        }
+    }
+}
+
+/// Calls `action` with every record of `data` that ends before `data` does,
+/// and returns where the unfinished last record starts. At the end of the
+/// input (`eof`) the last record counts as finished. Blank lines are skipped.
+fn split<F>(data: &[u8], eof: bool, offset: u64, delimiter: u8, action: &mut F) -> Result<usize, ErrorKind>
+where
+    F: FnMut(&Record) -> Result<(), ErrorKind>,
+{
+    let mut fields = Vec::new();
+    let mut start = 0;
+    while start < data.len() {
+        let Some(next) = split_record(&data[start..], eof, delimiter, &mut fields) else {
+            return Ok(start);
+        };
+        let blank = fields.len() == 1 && fields[0].start == fields[0].end && data[start] != b'"';
+        if !blank {
+            action(&Record { data: &data[start..], fields: &fields, offset: offset + start as u64 })?;
+        }
+        start += next;
+    }
+    Ok(start)
+}
+
+/// Calls `action` with every record of an input that is all in memory.
+pub fn for_each_record_in<F>(data: &[u8], delimiter: u8, mut action: F) -> Result<(), ErrorKind>
+where
+    F: FnMut(&Record) -> Result<(), ErrorKind>,
+{
+    split(data, true, 0, delimiter, &mut action).map(|_| ())
+}
+
+/// Calls `action` with every record of `reader`. Records of any length come
+/// out whole.
+pub fn for_each_record<R, F>(reader: R, delimiter: u8, mut action: F) -> Result<(), ErrorKind>
+where
+    R: Read,
+    F: FnMut(&Record) -> Result<(), ErrorKind>,
+{
+    buffer::read_records(reader, |data, offset, eof| split(data, eof, offset, delimiter, &mut action))
+}
+
+/// The fields of a header record, as column names.
+pub fn names(header: &Record) -> Vec<String> {
+    (0..header.len())
+        .map(|i| String::from_utf8_lossy(&header.field(i).unwrap_or_default()).trim().to_string())
+        .collect()
+}
+
+/// What column `i` is called: its header name, or its 1-based number.
+fn name(header: Option<&[String]>, i: usize) -> String {
+    let name = header.and_then(|names| names.get(i)).filter(|name| !name.is_empty()).cloned();
+    name.unwrap_or_else(|| format!("column {}", i + 1))
+}
+
+/// Whether the `first` record of a file looks like a header rather than
+/// data: some field of it is not a number where the `second` record holds
+/// one. A column of labels is no header, as it holds no numbers in either.
+/// Without a second record, a `first` that holds no number at all is a header.
+pub fn is_header(first: &Record, second: Option<&Record>, is_number: impl Fn(&[u8]) -> bool) -> bool {
+    let holds_number = |record: &Record, i| {
+        record.field(i).is_some_and(|field| !field.trim_ascii().is_empty() && is_number(&field))
+    };
+    match second {
+        Some(second) => {
+            (0..first.len()).any(|i| !is_number(&first.field(i).unwrap_or_default()) && holds_number(second, i))
+        }
+        None => !(0..first.len()).any(|i| holds_number(first, i)),
+    }
+}
+
+/// Finds the fields that hold `columns`, given the names from the header if
+/// the file has one, and names each column.
+pub fn resolve(columns: &[Column], header: Option<&[String]>) -> Result<(Vec<usize>, Vec<String>), ErrorKind> {
+    columns.iter()
+        .map(|column| match column {
+            Column::Index(i) => Ok((*i, name(header, *i))),
+            Column::Name(name) => match header.and_then(|names| names.iter().position(|field| field == name)) {
+                Some(i) => Ok((i, name.clone())),
+                None if header.is_none() => Err(ErrorKind::NoColumn(format!("no header to find column '{name}' in"))),
+                None => Err(ErrorKind::NoColumn(format!("no column '{name}' in the header"))),
+            },
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(|resolved| resolved.into_iter().unzip())
+}
+
+/// Picks the fields of the first data `record` that are numbers, and names
+/// each column.
+pub fn numeric(
+    record: &Record,
+    header: Option<&[String]>,
+    is_number: impl Fn(&[u8]) -> bool,
+) -> (Vec<usize>, Vec<String>) {
+    (0..record.len())
+        .filter(|&i| record.field(i).is_some_and(|field| !field.trim_ascii().is_empty() && is_number(&field)))
+        .map(|i| (i, name(header, i)))
+        .unzip()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{for_each_record, for_each_record_in, is_header, Record};
+    use crate::file_stat::testing::Trickle;
+
+    type Fields = Vec<(u64, Vec<String>)>;
+
+    fn fields(record: &Record) -> Vec<String> {
+        (0..record.len()).map(|i| String::from_utf8(record.field(i).unwrap().into_owned()).unwrap()).collect()
+    }
+
+    fn records(data: &str, delimiter: u8) -> Fields {
+        let mut records = Vec::new();
+        for_each_record_in(data.as_bytes(), delimiter, |record| {
+            records.push((record.offset, fields(record)));
+            Ok(())
+        })
+        .unwrap();
+
+        for max in [1, 2, 7] {
+            let mut read = Vec::new();
+            for_each_record(Trickle::new(data.as_bytes(), max), delimiter, |record| {
+                read.push((record.offset, fields(record)));
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(read, records, "reads of up to {max} bytes");
+        }
+        records
+    }
+
+    fn expected(records: &[(u64, &[&str])]) -> Fields {
+        records.iter().map(|(offset, fields)| (*offset, fields.iter().map(|field| field.to_string()).collect())).collect()
+    }
+
+    #[test]
+    fn splits_records_into_fields() {
+        assert_eq!(records("1,2,3\n4,,6\n", b','), expected(&[(0, &["1", "2", "3"]), (6, &["4", "", "6"])]));
+        assert_eq!(records("1\t2\n 3 \t4", b'\t'), expected(&[(0, &["1", "2"]), (4, &[" 3 ", "4"])]));
+        // Blank lines are no records, but a line of one empty quoted field is.
+        assert_eq!(records("\n1\n\n\"\"\n\n", b','), expected(&[(1, &["1"]), (4, &[""])]));
+    }
+
+    #[test]
+    fn quoted_fields_hold_delimiters_quotes_and_line_breaks() {
+        let data = "\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\"\n\"\"\"\",\"\",x\n";
+        assert_eq!(records(data, b','), expected(&[(0, &["a,b", "say \"hi\"", "two\nlines"]), (31, &["\"", "", "x"])]));
+        // Anything between the closing quote and the delimiter is dropped.
+        assert_eq!(records("\"1\"2,3\n", b','), expected(&[(0, &["1", "3"])]));
+        // A quote that is never closed runs to the end of the input.
+        assert_eq!(records("1,\"2\n3", b','), expected(&[(0, &["1", "2\n3"])]));
+    }
+
+    #[test]
+    fn crlf_line_breaks_are_not_part_of_fields() {
+        let data = "1,2\r\n3,\"4\"\r\n\r\n5\r,6\r\n";
+        assert_eq!(records(data, b','), expected(&[(0, &["1", "2"]), (5, &["3", "4"]), (14, &["5\r", "6"])]));
+    }
+
+    #[test]
+    fn field_offsets_are_in_the_input() {
+        let data = b"1,22\n\"3\",  4\n";
+        let mut offsets = Vec::new();
+        for_each_record_in(data, b',', |record| {
+            offsets.extend((0..record.len()).map(|i| record.field_offset(i)));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(offsets, [0, 2, 6, 9]);
+    }
+
+    #[test]
+    fn guesses_headers() {
+        let is_number = |field: &[u8]| {
+            let field = std::str::from_utf8(field).unwrap().trim();
+            field.is_empty() || field.parse::<f64>().is_ok()
+        };
+        let guess = |data: &str| {
+            let mut records = Vec::new();
+            for_each_record_in(data.as_bytes(), b',', |record| {
+                records.push(record.to_buf());
+                Ok(())
+            })
+            .unwrap();
+            let records: Vec<Record> = records.iter().map(|record| record.record()).collect();
+            is_header(&records[0], records.get(1), is_number)
+        };
+        assert!(guess("a,b\n1,2\n"));
+        assert!(guess("name,a,b\nfoo,1,2\n"));
+        assert!(guess(",a\n1,2\n"));
+        assert!(guess("a,b\n"));
+        assert!(guess("\"1\",x\n1,2\n"));
+        // A column of labels is not a header.
+        assert!(!guess("foo,1,2\nbar,3,4\n"));
+        assert!(!guess("1,2\n3,4\n"));
+        assert!(!guess("x,1\n"));
+        // Nothing to compare with where the second record has no number.
+        assert!(!guess("a,b\n,\n"));
+        assert!(!guess("a,b\nc\n"));
+    }
+}
@@ -0,0 +1,204 @@
+use std::fmt;
+use std::io;
+use std::num::ParseFloatError;
+
+use super::Element;
+
+/// An error together with the input it happened in.
+#[derive(Debug)]
+pub struct Error {
+    /// The input, as it was named.
+    pub path: String,
+    /// What went wrong.
+    pub kind: ErrorKind,
+}
+
+/// What went wrong while reading an input. Bad tokens are quoted in part only.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The input could not be opened or read.
+    Io(io::Error),
+    /// A token that is not UTF-8 text.
+    Utf8 {
+        /// Where the token is.
+        location: Location,
+        /// The token, with anything that is not UTF-8 replaced.
+        token: String,
+    },
+    /// A token that is not a number.
+    Parse {
+        /// Where the token is.
+        location: Location,
+        /// The token.
+        token: String,
+        /// Why it is not a number.
+        source: ParseFloatError,
+    },
+    /// NaN or ±inf, which [`NonFinite::Error`](super::NonFinite::Error) counts as bad.
+    NonFinite {
+        /// Where the value is.
+        location: Location,
+        /// The value as it was written, or as it reads for binary input.
+        token: String,
+    },
+    /// A record without the field that a value should come from.
+    Missing {
+        /// Where the record starts.
+        location: Location,
+        /// The name or dotted path of the field.
+        field: String,
+    },
+    /// A line of JSON Lines input that cannot be parsed as far as the field.
+    Json {
+        /// Where the line starts.
+        location: Location,
+        /// What is wrong with it, and at which column.
+        message: String,
+    },
+    /// Raw binary input that ends in part of an element.
+    TrailingBytes {
+        /// Length of the input in bytes.
+        len: u64,
+        /// The element the input is made of.
+        element: Element,
+        /// Bytes left over after the last whole element.
+        trailing: usize,
+    },
+    /// A `.npy` file with a header that cannot be used, or that does not match the data.
+    Npy(String),
+    /// A column that is asked for but cannot be found.
+    NoColumn(String),
+    /// An input with no values to take a statistic of.
+    NoValues,
+    /// An input that gave different values on a later pass than on the first,
+    /// such as a file that is still being written.
+    Changed,
+}
+
+/// Where a bad token starts: its byte offset and the index of the value it was meant to be.
+#[derive(Clone, Copy, Debug)]
+pub struct Location {
+    /// Byte offset in the (decompressed) input.
+    pub offset: u64,
+    /// Index of the value, counting from 0.
+    pub index: u64,
+}
+
+/// Bad tokens are quoted in messages, but only up to this many bytes.
+const EXCERPT_LEN: usize = 40;
+
+impl ErrorKind {
+    pub(super) fn utf8(location: Location, token: &[u8]) -> Self {
+        ErrorKind::Utf8 { location, token: excerpt(token) }
+    }
+
+    pub(super) fn parse(location: Location, token: &[u8], source: ParseFloatError) -> Self {
+        ErrorKind::Parse { location, token: excerpt(token), source }
+    }
+
+    pub(super) fn non_finite(location: Location, token: &[u8]) -> Self {
+        ErrorKind::NonFinite { location, token: excerpt(token) }
+    }
+
+    pub(super) fn missing(location: Location, field: &str) -> Self {
+        ErrorKind::Missing { location, field: field.to_string() }
+    }
+
+    pub(super) fn json(location: Location, message: String) -> Self {
+        ErrorKind::Json { location, message }
+    }
+
+    /// Moves the location forward by `count` values, for errors found in a
+    /// chunk that did not start at the first value.
+    pub(super) fn shift_index(&mut self, count: u64) {
+        match self {
+            ErrorKind::Utf8 { location, .. }
+            | ErrorKind::Parse { location, .. }
+            | ErrorKind::NonFinite { location, .. }
+            | ErrorKind::Missing { location, .. }
+            | ErrorKind::Json { location, .. } => location.index += count,
+            ErrorKind::Io(_)
+            | ErrorKind::TrailingBytes { .. }
+            | ErrorKind::Npy(_)
+            | ErrorKind::NoColumn(_)
+            | ErrorKind::NoValues
+            | ErrorKind::Changed => {}
+        }
+    }
+
+    pub(super) fn at(self, path: &str) -> Error {
+        Error { path: path.to_string(), kind: self }
+    }
+}
+
+fn excerpt(token: &[u8]) -> String {
+    if token.len() <= EXCERPT_LEN {
+        return String::from_utf8_lossy(token).into_owned();
+    }
+    format!("{}...", String::from_utf8_lossy(&token[..EXCERPT_LEN]))
+}
+
+impl From<io::Error> for ErrorKind {
+    fn from(err: io::Error) -> Self {
+        ErrorKind::Io(err)
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "value {} at byte {}", self.index, self.offset)
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Io(err) => write!(f, "{err}"),
+            ErrorKind::Utf8 { location, token } => write!(f, "invalid UTF-8 in '{token}' ({location})"),
+            ErrorKind::Parse { location, token, source } => write!(f, "invalid number '{token}' ({location}): {source}"),
+            ErrorKind::NonFinite { location, token } => write!(f, "non-finite value '{token}' ({location})"),
+            ErrorKind::Missing { location, field } => write!(f, "missing field '{field}' ({location})"),
+            ErrorKind::Json { location, message } => write!(f, "invalid JSON: {message} ({location})"),
+            ErrorKind::TrailingBytes { len, element, trailing } => write!(
+                f,
+                "{trailing} trailing bytes: length {len} is not a multiple of the {}-byte {element} element",
+                element.size()
+            ),
+            ErrorKind::Npy(message) => write!(f, "bad .npy file: {message}"),
+            ErrorKind::NoColumn(message) => write!(f, "{message}"),
+            ErrorKind::NoValues => write!(f, "no values"),
+            ErrorKind::Changed => write!(f, "input changed between passes"),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path == "-" { "<stdin>" } else { &self.path };
+        write!(f, "{path}: {}", self.kind)
+    }
+}
+
+/// The messages of the I/O and parse errors underneath are part of the
+/// message already, so they are not given again as a source.
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+
+    use super::{ErrorKind, Location};
+
+    #[test]
+    fn underlying_errors_are_told_once() {
+        let source = "x".parse::<f64>().unwrap_err();
+        let err = ErrorKind::parse(Location { offset: 4, index: 2 }, b"x", source).at("in.txt");
+        assert_eq!(err.to_string(), "in.txt: invalid number 'x' (value 2 at byte 4): invalid float literal");
+        assert!(err.source().is_none());
+
+        let err = ErrorKind::from(std::io::Error::other("disk on fire")).at("in.txt");
+        assert_eq!(err.to_string(), "in.txt: disk on fire");
+        assert!(err.source().is_none());
+    }
+}
@@ -0,0 +1,337 @@
+//! Decimal to `f64` conversion straight from bytes.
+//!
+//! [`parse`] covers the plain decimal syntax that makes up nearly all real
+//! data: an optional sign, digits with an optional point, and an optional
+//! exponent. It gives bit-for-bit the same result as `str::parse::<f64>`, and
+//! returns `None` for anything it does not handle (`inf`, `nan`, malformed
+//! tokens, and the rare inputs Eisel-Lemire cannot round on its own), so that
+//! the caller can fall back to `str::parse`, which also produces the error.
+
+mod table;
+
+use table::{LARGEST_POWER_OF_FIVE, POWER_OF_FIVE_128, SMALLEST_POWER_OF_FIVE};
+
+const MANTISSA_BITS: u32 = 52;
+const MINIMUM_EXPONENT: i32 = -1023;
+const INFINITE_POWER: i32 = 0x7ff;
+
+/// More significant digits than this do not fit into a `u64`.
+const MAX_DIGITS: u32 = 19;
+
+/// Exponents beyond this are infinity or zero whatever the digits; bigger
+/// ones are clamped so that adding them up cannot overflow.
+const MAX_EXPONENT: i64 = 1 << 20;
+
+pub fn parse(s: &[u8]) -> Option<f64> {
+    let (negative, s) = match s.first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+
+    let mut i = 0;
+    let mut mantissa = 0u64;
+    let mut digits = 0;
+    let mut exponent = 0i64;
+    // Some nonzero digit did not fit into `mantissa`.
+    let mut truncated = false;
+    let mut any_digit = false;
+
+    let mut digit = |d: u8, fraction: bool, exponent: &mut i64| {
+        any_digit = true;
+        if mantissa == 0 && d == 0 {
+            // A leading zero only matters for where the point is.
+            *exponent -= fraction as i64;
+        } else if digits < MAX_DIGITS {
+            mantissa = mantissa * 10 + d as u64;
+            digits += 1;
+            *exponent -= fraction as i64;
+        } else {
+            truncated |= d != 0;
+            *exponent += !fraction as i64;
+        }
+    };
+
+    while i < s.len() && s[i].is_ascii_digit() {
+        digit(s[i] - b'0', false, &mut exponent);
+        i += 1;
+    }
+    if i < s.len() && s[i] == b'.' {
+        i += 1;
+        while i < s.len() && s[i].is_ascii_digit() {
+            digit(s[i] - b'0', true, &mut exponent);
+            i += 1;
+        }
+    }
+    if !any_digit {
+        return None;
+    }
+
+    if i < s.len() && (s[i] == b'e' || s[i] == b'E') {
+        i += 1;
+        let negative_exponent = match s.get(i) {
+            Some(b'-') => {
+                i += 1;
+                true
+            }
+            Some(b'+') => {
+                i += 1;
+                false
+            }
+            _ => false,
+        };
+        let start = i;
+        let mut explicit = 0i64;
+        while i < s.len() && s[i].is_ascii_digit() {
+            explicit = (explicit * 10 + (s[i] - b'0') as i64).min(MAX_EXPONENT);
+            i += 1;
+        }
+        if i == start {
+            return None;
+        }
+        exponent += if negative_exponent { -explicit } else { explicit };
+    }
+    if i != s.len() {
+        return None;
+    }
+
+    let value = if truncated {
+        // The value lies between the truncated digits and the next number up;
+        // if both round to the same float, so does the value.
+        let low = to_float(exponent, mantissa)?;
+        let high = to_float(exponent, mantissa + 1)?;
+        if low.to_bits() != high.to_bits() {
+            return None;
+        }
+        low
+    } else {
+        to_float(exponent, mantissa)?
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// `w * 10^q`, correctly rounded, or `None` when that cannot be decided here.
+fn to_float(q: i64, w: u64) -> Option<f64> {
+    const POWERS_OF_TEN: [f64; 23] = [
+        1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11,
+        1e12, 1e13, 1e14, 1e15, 1e16, 1e17, 1e18, 1e19, 1e20, 1e21, 1e22,
+    ];
+
+    // Clinger's fast path: both operands are exact, so the one rounding of the
+    // multiplication or division is the correct one.
+    if w <= 1 << 53 && (-22..=22).contains(&q) {
+        let w = w as f64;
+        return Some(if q < 0 { w / POWERS_OF_TEN[-q as usize] } else { w * POWERS_OF_TEN[q as usize] });
+    }
+
+    let (mantissa, power2) = eisel_lemire(q, w)?;
+    Some(f64::from_bits(mantissa | (power2 as u64) << MANTISSA_BITS))
+}
+
+/// The Eisel-Lemire algorithm: the mantissa and biased exponent of `w * 10^q`,
+/// computed from a 128-bit approximation of `5^q`.
+fn eisel_lemire(q: i64, mut w: u64) -> Option<(u64, i32)> {
+    if w == 0 || q < SMALLEST_POWER_OF_FIVE {
+        return Some((0, 0));
+    }
+    if q > LARGEST_POWER_OF_FIVE {
+        return Some((0, INFINITE_POWER));
+    }
+
+    let leading_zeros = w.leading_zeros();
+    w <<= leading_zeros;
+    let (lo, hi) = product_approx(q, w, MANTISSA_BITS + 3);
+    // The truncated product may be one short of carrying into `hi`; that is
+    // only impossible to rule out when `5^q` is not exact in 128 bits.
+    if lo == u64::MAX && !(-27..=55).contains(&q) {
+        return None;
+    }
+
+    let upper_bit = (hi >> 63) as i32;
+    let shift = upper_bit + 64 - MANTISSA_BITS as i32 - 3;
+    let mut mantissa = hi >> shift;
+    let mut power2 = power(q as i32) + upper_bit - leading_zeros as i32 - MINIMUM_EXPONENT;
+
+    if power2 <= 0 {
+        // Subnormal, or rounding up to the smallest normal.
+        if -power2 + 1 >= 64 {
+            return Some((0, 0));
+        }
+        mantissa >>= -power2 + 1;
+        mantissa += mantissa & 1;
+        mantissa >>= 1;
+        power2 = (mantissa >= 1 << MANTISSA_BITS) as i32;
+        return Some((mantissa, power2));
+    }
+
+    // Exactly halfway between two floats: round to even instead of up.
+    if lo <= 1 && (-4..=23).contains(&q) && mantissa & 3 == 1 && mantissa << shift == hi {
+        mantissa &= !1;
+    }
+    mantissa += mantissa & 1;
+    mantissa >>= 1;
+    if mantissa >= 2 << MANTISSA_BITS {
+        mantissa = 1 << MANTISSA_BITS;
+        power2 += 1;
+    }
+    mantissa &= !(1 << MANTISSA_BITS);
+    if power2 >= INFINITE_POWER {
+        return Some((0, INFINITE_POWER));
+    }
+    Some((mantissa, power2))
+}
+
+/// `floor(log2(10^q)) + 63`, close enough for every `q` in the table.
+fn power(q: i32) -> i32 {
+    (q.wrapping_mul(152_170 + 65536) >> 16) + 63
+}
+
+fn full_multiplication(a: u64, b: u64) -> (u64, u64) {
+    let r = a as u128 * b as u128;
+    (r as u64, (r >> 64) as u64)
+}
+
+/// The top 128 bits of `w * 5^q`, where only the `precision` top bits need
+/// to be right; the low half of `5^q` is only used when they might not be.
+fn product_approx(q: i64, w: u64, precision: u32) -> (u64, u64) {
+    let mask = u64::MAX >> precision;
+    let (hi5, lo5) = POWER_OF_FIVE_128[(q - SMALLEST_POWER_OF_FIVE) as usize];
+
+    let (mut first_lo, mut first_hi) = full_multiplication(w, hi5);
+    if first_hi & mask == mask {
+        let (_, second_hi) = full_multiplication(w, lo5);
+        first_lo = first_lo.wrapping_add(second_hi);
+        if second_hi > first_lo {
+            first_hi += 1;
+        }
+    }
+    (first_lo, first_hi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::file_stat::testing::Rng;
+
+    fn check(s: &str) {
+        let expected = s.parse::<f64>().ok();
+        match parse(s.as_bytes()) {
+            Some(x) => {
+                let expected = expected.unwrap_or_else(|| panic!("'{s}' parsed as {x} but str::parse rejects it"));
+                assert_eq!(x.to_bits(), expected.to_bits(), "'{s}': {x:e} != {expected:e}");
+            }
+            // Only non-finite values and long inputs should need the fallback.
+            None => assert!(
+                expected.is_none_or(|x| !x.is_finite() || significant_digits(s) > 19),
+                "'{s}' was left to the fallback",
+            ),
+        }
+    }
+
+    fn significant_digits(s: &str) -> usize {
+        let mantissa = s.split(['e', 'E']).next().unwrap();
+        mantissa.trim_start_matches(['+', '-', '0', '.']).chars().filter(char::is_ascii_digit).count()
+    }
+
+    #[test]
+    fn edge_cases() {
+        for s in [
+            "0", "-0", "+0", "0.0", "-0.0", "00000", "0e0", "0e999999999", "1", "-1", "+1", "1.", ".5", "-.5",
+            "1e0", "1E5", "1e+5", "1e-5", "1.5e3", "123456789", "0.1", "0.2", "0.3", "3.14159",
+            "9007199254740992", "9007199254740993", "9007199254740995",
+            "18446744073709551615", "18446744073709551616", "123456789012345678901234567890",
+            "0.000000000000000000000000000000000000001",
+            "1.7976931348623157e308", "1.7976931348623158e308", "1.7976931348623159e308", "1e308", "1e309",
+            "2.2250738585072014e-308", "2.2250738585072011e-308", "2.2250738585072012e-308",
+            "4.9406564584124654e-324", "5e-324", "2.4703282292062327e-324", "2.4703282292062328e-324",
+            "1e-400", "1e400", "1e-999999999999999999999", "1e999999999999999999999",
+            "7.2057594037927933e16", "2.2250738585072013e-308",
+            "0.1000000000000000055511151231257827021181583404541015625",
+            "0.10000000000000000555111512312578270211815834045410156250000000001",
+            "1.00000000000000011102230246251565404236316680908203125",
+            "1.00000000000000011102230246251565404236316680908203124",
+            "1.00000000000000011102230246251565404236316680908203126",
+            "3.819822494817282e-8", "38.67522961537808", "1.3862322506737415",
+            "", "-", "+", ".", "e5", "1e", "1e+", "1e-", "1.2.3", "1e5.5", "--1", "+-1", " 1", "1 ", "1_000",
+            "0x10", "inf", "-inf", "infinity", "NaN", "nan", "N/A", "1d5", "1,5",
+        ] {
+            check(s);
+        }
+    }
+
+    #[test]
+    fn round_trips_random_floats() {
+        let mut rng = Rng(0x1234_5678_9abc_def1);
+        for _ in 0..200_000 {
+            let x = f64::from_bits(rng.next());
+            if !x.is_finite() {
+                continue;
+            }
+            check(&format!("{x}"));
+            check(&format!("{x:e}"));
+            check(&format!("{x:?}"));
+            check(&format!("{:.*e}", rng.below(25) as usize, x));
+        }
+    }
+
+    #[test]
+    fn subnormals_and_extremes() {
+        let mut rng = Rng(0xdead_beef_cafe_f00d);
+        for _ in 0..50_000 {
+            let subnormal = f64::from_bits(rng.below(1 << 52));
+            check(&format!("{subnormal:e}"));
+            check(&format!("{:.*e}", rng.below(30) as usize, subnormal));
+            let huge = f64::from_bits(0x7fe0_0000_0000_0000 | rng.below(1 << 52));
+            check(&format!("{huge:e}"));
+            check(&format!("{:.*e}", rng.below(30) as usize, huge));
+        }
+    }
+
+    #[test]
+    fn random_digit_strings() {
+        let mut rng = Rng(0x0bad_5eed_0bad_5eed);
+        for _ in 0..300_000 {
+            let mut s = String::new();
+            match rng.below(3) {
+                0 => s.push('-'),
+                1 => s.push('+'),
+                _ => {}
+            }
+            for _ in 0..rng.below(30) {
+                s.push((b'0' + rng.below(10) as u8) as char);
+            }
+            if rng.below(2) == 0 {
+                s.push('.');
+                for _ in 0..rng.below(30) {
+                    s.push((b'0' + rng.below(10) as u8) as char);
+                }
+            }
+            if rng.below(2) == 0 {
+                s.push(if rng.below(2) == 0 { 'e' } else { 'E' });
+                let exponent = rng.below(700) as i64 - 350;
+                s.push_str(&exponent.to_string());
+            }
+            check(&s);
+        }
+    }
+
+    #[test]
+    fn halfway_cases() {
+        let mut rng = Rng(0x5151_5151_5151_5151);
+        for _ in 0..100_000 {
+            // Integers exactly halfway between two neighbouring floats, which
+            // must round to the even one, plus their neighbours.
+            let low = (rng.below(1 << 62) | 1 << 53) as f64;
+            let high = f64::from_bits(low.to_bits() + 1);
+            let halfway = (low as u64 / 2 + high as u64 / 2) as u128 + (low as u64 % 2 + high as u64 % 2) as u128 / 2;
+            for n in [halfway - 1, halfway, halfway + 1] {
+                let digits = n.to_string();
+                check(&digits);
+                check(&format!("{digits}.000"));
+                // The same number with the point moved into the digits.
+                let point = rng.below(digits.len() as u64) as usize;
+                check(&format!("{}.{}e{}", &digits[..point], &digits[point..], digits.len() - point));
+            }
+        }
+    }
+}
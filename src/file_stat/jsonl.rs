@@ -0,0 +1,326 @@
+use std::io::Read;
+
+use super::{buffer, ErrorKind};
+
+/// Calls `action` with every line of `data` that is not blank, and where it
+/// starts in the input, given that `data` starts at `offset`. Returns where
+/// the unfinished last line starts; at the end of the input (`eof`) the last
+/// line counts as finished.
+fn split<F>(data: &[u8], eof: bool, offset: u64, action: &mut F) -> Result<usize, ErrorKind>
+where
+    F: FnMut(&[u8], u64) -> Result<(), ErrorKind>,
+{
+    let mut start = 0;
+    while start < data.len() {
+        let end = match data[start..].iter().position(|&b| b == b'\n') {
+            Some(len) => start + len,
+            None if eof => data.len(),
+            None => return Ok(start),
+        };
+        let line = &data[start..end];
+        if !line.trim_ascii().is_empty() {
+            action(line, offset + start as u64)?;
+        }
+        start = end + 1;
+    }
+    Ok(data.len())
+}
+
+/// Calls `action` with every line of an input that is all in memory.
+pub fn for_each_line_in<F>(data: &[u8], offset: u64, mut action: F) -> Result<(), ErrorKind>
+where
+    F: FnMut(&[u8], u64) -> Result<(), ErrorKind>,
+{
+    split(data, true, offset, &mut action).map(|_| ())
+}
+
+/// Calls `action` with every line of `reader` that is not blank. Lines of
+/// any length come out whole.
+pub fn for_each_line<R, F>(reader: R, mut action: F) -> Result<(), ErrorKind>
+where
+    R: Read,
+    F: FnMut(&[u8], u64) -> Result<(), ErrorKind>,
+{
+    buffer::read_records(reader, |data, offset, eof| split(data, eof, offset, &mut action))
+}
+
+/// Finds the field at the dotted `path`, such as `resp.timing.total`, in a
+/// `line` that holds a JSON object. A part of the path that is a number also
+/// picks an element of an array.
+///
+/// Returns where the value starts in the line and its text: the contents of a
+/// string, still escaped, or the whole of anything else. `None` means the line
+/// has no such field. Only as much of the line as the search goes through is
+/// checked.
+pub fn field<'a>(line: &'a [u8], path: &str) -> Result<Option<(usize, &'a [u8])>, String> {
+    let mut parser = Parser { data: line, pos: 0 };
+    if !matches!(parser.peek(), Some(b'{' | b'[')) {
+        return Err("not a JSON object".to_string());
+    }
+    for part in path.split('.') {
+        let found = match parser.peek() {
+            Some(b'{') => parser.member(part)?,
+            Some(b'[') => match part.parse() {
+                Ok(index) => parser.element(index)?,
+                Err(_) => false,
+            },
+            _ => false,
+        };
+        if !found {
+            return Ok(None);
+        }
+    }
+    parser.value().map(Some)
+}
+
+/// Just enough of a JSON parser to walk down to one field of a line.
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// The next byte that is not whitespace.
+    fn peek(&mut self) -> Option<u8> {
+        while matches!(self.data.get(self.pos), Some(b' ' | b'\t' | b'\r' | b'\n')) {
+            self.pos += 1;
+        }
+        self.data.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, b: u8) -> bool {
+        if self.peek() == Some(b) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, b: u8) -> Result<(), String> {
+        if self.eat(b) {
+            return Ok(());
+        }
+        match self.peek() {
+            Some(found) => Err(format!("expected '{}', found '{}' at column {}", b as char, found as char, self.pos + 1)),
+            None => Err(format!("expected '{}', found the end of the line", b as char)),
+        }
+    }
+
+    /// Moves past the string that starts here and returns its contents.
+    fn string(&mut self) -> Result<&'a [u8], String> {
+        let start = self.pos + 1;
+        let mut i = start;
+        loop {
+            match self.data.get(i) {
+                None => return Err("unterminated string".to_string()),
+                Some(b'"') => break,
+                Some(b'\\') => i += 2,
+                Some(_) => i += 1,
+            }
+        }
+        self.pos = i + 1;
+        Ok(&self.data[start..i])
+    }
+
+    /// Moves past the value that starts here. See [`field`] for what is returned.
+    fn value(&mut self) -> Result<(usize, &'a [u8]), String> {
+        match self.peek() {
+            None => Err("expected a value, found the end of the line".to_string()),
+            Some(b'"') => Ok((self.pos + 1, self.string()?)),
+            Some(b'{' | b'[') => {
+                let start = self.pos;
+                let mut depth = 0;
+                loop {
+                    match self.data.get(self.pos) {
+                        None => return Err("unterminated object or array".to_string()),
+                        Some(b'"') => {
+                            self.string()?;
+                            continue;
+                        }
+                        Some(b'{' | b'[') => depth += 1,
+                        Some(b'}' | b']') => depth -= 1,
+                        Some(_) => {}
+                    }
+                    self.pos += 1;
+                    if depth == 0 {
+                        return Ok((start, &self.data[start..self.pos]));
+                    }
+                }
+            }
+            Some(_) => {
+                let start = self.pos;
+                while !matches!(self.data.get(self.pos), None | Some(b',' | b'}' | b']' | b' ' | b'\t' | b'\r' | b'\n')) {
+                    self.pos += 1;
+                }
+                if self.pos == start {
+                    return Err(format!("expected a value at column {}", start + 1));
+                }
+                Ok((start, &self.data[start..self.pos]))
+            }
+        }
+    }
+
+    /// Moves into the object that starts here, up to the value of member
+    /// `name`, if it has one.
+    fn member(&mut self, name: &str) -> Result<bool, String> {
+        self.expect(b'{')?;
+        if self.eat(b'}') {
+            return Ok(false);
+        }
+        loop {
+            if self.peek() != Some(b'"') {
+                return Err(format!("expected a key at column {}", self.pos + 1));
+            }
+            let key = self.string()?;
+            self.expect(b':')?;
+            let matches = if key.contains(&b'\\') { unescape(key) == name.as_bytes() } else { key == name.as_bytes() };
+            if matches {
+                return Ok(true);
+            }
+            self.value()?;
+            if !self.eat(b',') {
+                self.expect(b'}')?;
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Moves into the array that starts here, up to element `index`, if it has one.
+    fn element(&mut self, index: usize) -> Result<bool, String> {
+        self.expect(b'[')?;
+        if self.eat(b']') {
+            return Ok(false);
+        }
+        for _ in 0..index {
+            self.value()?;
+            if !self.eat(b',') {
+                self.expect(b']')?;
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// The text of a string with escapes, as UTF-8. Bad escapes are kept as they are.
+fn unescape(raw: &[u8]) -> Vec<u8> {
+    let mut text = Vec::with_capacity(raw.len());
+    let mut i = 0;
+    while i < raw.len() {
+        if raw[i] != b'\\' || i + 1 == raw.len() {
+            text.push(raw[i]);
+            i += 1;
+            continue;
+        }
+        let escaped = match raw[i + 1] {
+            b'b' => b'\x08',
+            b'f' => b'\x0c',
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b'u' => {
+                let hex = |at: usize| {
+                    raw.get(at..at + 4)
+                        .and_then(|digits| std::str::from_utf8(digits).ok())
+                        .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                };
+                let (c, len) = match hex(i + 2) {
+                    Some(high @ 0xd800..=0xdbff) => match (raw.get(i + 6..i + 8), hex(i + 8)) {
+                        (Some(b"\\u"), Some(low @ 0xdc00..=0xdfff)) => {
+                            (char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)), 12)
+                        }
+                        _ => (None, 6),
+                    },
+                    Some(code) => (char::from_u32(code), 6),
+                    None => (None, 2),
+                };
+                match c {
+                    Some(c) => text.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+                    None => text.extend_from_slice(&raw[i..i + len]),
+                }
+                i += len;
+                continue;
+            }
+            other => other,
+        };
+        text.push(escaped);
+        i += 2;
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{field, unescape};
+
+    fn text(line: &str, path: &str) -> Option<String> {
+        field(line.as_bytes(), path).unwrap().map(|(_, text)| String::from_utf8(text.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn finds_fields_by_dotted_path() {
+        let line = r#" { "a": 1, "resp": {"timing" : {"total": -2.5e3 }, "codes": [200, [3, 4], {"x": "y"}]}, "s": "q\"z" }"#;
+        assert_eq!(text(line, "a").as_deref(), Some("1"));
+        assert_eq!(text(line, "resp.timing.total").as_deref(), Some("-2.5e3"));
+        assert_eq!(text(line, "resp.codes.0").as_deref(), Some("200"));
+        assert_eq!(text(line, "resp.codes.1.1").as_deref(), Some("4"));
+        assert_eq!(text(line, "resp.codes.2.x").as_deref(), Some("y"));
+        assert_eq!(text(line, "resp.codes.1").as_deref(), Some("[3, 4]"));
+        assert_eq!(text(line, "resp.timing").as_deref(), Some(r#"{"total": -2.5e3 }"#));
+        // Strings come out still escaped.
+        assert_eq!(text(line, "s").as_deref(), Some(r#"q\"z"#));
+        // Top-level arrays are indexed as well.
+        assert_eq!(text("[1, [2, 3]]", "1.0").as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn gives_where_the_value_starts() {
+        let line = br#"{"a": {"b": 12}, "c": "xy"}"#;
+        assert_eq!(field(line, "a.b").unwrap(), Some((12, &b"12"[..])));
+        assert_eq!(field(line, "c").unwrap(), Some((23, &b"xy"[..])));
+    }
+
+    #[test]
+    fn looks_past_keys_in_strings_and_nested_values() {
+        let line = r#"{"x": "\"a\": 1, \\", "y": {"a": 2, "z": "}"}, "w": ["a", {"a": 3}], "a": 4}"#;
+        assert_eq!(text(line, "a").as_deref(), Some("4"));
+        // Keys are compared unescaped.
+        assert_eq!(text(r#"{"a\n": 5}"#, "a\n").as_deref(), Some("5"));
+    }
+
+    #[test]
+    fn missing_fields_are_none() {
+        let line = r#"{"a": {"b": 1}, "c": [1, 2], "d": 3, "e": {}, "f": []}"#;
+        for path in ["b", "a.c", "a.b.c", "c.2", "c.x", "d.0", "e.a", "f.0", "A"] {
+            assert_eq!(field(line.as_bytes(), path).unwrap(), None, "{path}");
+        }
+    }
+
+    #[test]
+    fn reports_lines_that_are_not_json() {
+        for (line, path, message) in [
+            ("1", "a", "not a JSON object"),
+            ("", "a", "not a JSON object"),
+            (r#"{"a" 1}"#, "a", "expected ':', found '1' at column 6"),
+            (r#"{"b": 1 "a": 2}"#, "a", "expected '}', found '\"' at column 9"),
+            (r#"{"b": 1, a: 2}"#, "a", "expected a key at column 10"),
+            (r#"{"a": "1}"#, "a", "unterminated string"),
+            (r#"{"b": {"c": [1, 2}"#, "a", "unterminated object or array"),
+            (r#"{"a": }"#, "a", "expected a value at column 7"),
+            (r#"{"a":"#, "a", "expected a value, found the end of the line"),
+            (r#"{"b": 1"#, "a", "expected '}', found the end of the line"),
+        ] {
+            assert_eq!(field(line.as_bytes(), path), Err(message.to_string()), "{line}");
+        }
+        // Only as much of the line as the search goes through is checked.
+        assert_eq!(text(r#"{"a": 1, "b": ]]"#, "a").as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn unescapes_strings() {
+        assert_eq!(unescape(br#"a\"\\\/\b\f\n\r\t"#), b"a\"\\/\x08\x0c\n\r\t");
+        assert_eq!(unescape(br#"\u00e9\ud83d\ude00"#), "é😀".as_bytes());
+        // Bad escapes and lone surrogates are kept as they are.
+        assert_eq!(unescape(br#"\ud83d\u12\x\"#), br#"\ud83d\u12x\"#);
+    }
+}
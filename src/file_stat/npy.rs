@@ -0,0 +1,387 @@
+use std::io::{self, Read};
+
+use super::{Element, ErrorKind};
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// What the header of a `.npy` file says about the array after it.
+pub struct Header {
+    pub element: Element,
+    pub fortran_order: bool,
+    pub shape: Vec<u64>,
+    /// Length of the header, which is where the data starts.
+    pub len: u64,
+}
+
+fn error(message: impl Into<String>) -> ErrorKind {
+    ErrorKind::Npy(message.into())
+}
+
+impl Header {
+    /// Reads the header from the start of `reader`, leaving it at the data.
+    pub fn read(mut reader: impl Read) -> Result<Self, ErrorKind> {
+        let mut prefix = [0u8; 12];
+        reader.read_exact(&mut prefix[..10]).map_err(truncated)?;
+        let dict_start = match prefix[6] {
+            1 => 10,
+            _ => {
+                reader.read_exact(&mut prefix[10..]).map_err(truncated)?;
+                12
+            }
+        };
+        let len = Self::len(&prefix[..dict_start])?;
+
+        let mut header = prefix[..dict_start].to_vec();
+        header.resize(len as usize, 0);
+        reader.read_exact(&mut header[dict_start..]).map_err(truncated)?;
+        Self::parse(&header)
+    }
+
+    /// Parses the header at the start of `data`, which may go on past it.
+    pub fn parse(data: &[u8]) -> Result<Self, ErrorKind> {
+        let len = Self::len(data)?;
+        let dict_start = if data[6] == 1 { 10 } else { 12 };
+        let dict = data.get(dict_start..len as usize).ok_or_else(|| error("truncated header"))?;
+        let dict = std::str::from_utf8(dict).map_err(|_| error("header is not text"))?;
+
+        let mut descr = None;
+        let mut fortran_order = None;
+        let mut shape = None;
+        let mut parser = Parser(dict.trim_end());
+        parser.expect('{')?;
+        while !parser.eat('}') {
+            let key = parser.string()?;
+            parser.expect(':')?;
+            match key {
+                "descr" => descr = Some(parser.string()?),
+                "fortran_order" => fortran_order = Some(parser.boolean()?),
+                "shape" => shape = Some(parser.tuple()?),
+                _ => return Err(error(format!("unexpected key '{key}' in header"))),
+            }
+            if !parser.eat(',') {
+                parser.expect('}')?;
+                break;
+            }
+        }
+
+        let descr = descr.ok_or_else(|| error("header has no 'descr'"))?;
+        Ok(Header {
+            element: dtype(descr).ok_or_else(|| error(format!("unsupported dtype '{descr}'")))?,
+            fortran_order: fortran_order.ok_or_else(|| error("header has no 'fortran_order'"))?,
+            shape: shape.ok_or_else(|| error("header has no 'shape'"))?,
+            len,
+        })
+    }
+
+    /// The total header length given by the magic string, version and length prefix.
+    fn len(data: &[u8]) -> Result<u64, ErrorKind> {
+        if data.len() < 10 || !data.starts_with(MAGIC) {
+            return Err(error("not a .npy file"));
+        }
+        match data[6] {
+            1 => Ok(10 + u16::from_le_bytes([data[8], data[9]]) as u64),
+            2 | 3 if data.len() >= 12 => Ok(12 + u32::from_le_bytes(data[8..12].try_into().unwrap()) as u64),
+            2 | 3 => Err(error("truncated header")),
+            version => Err(error(format!("unsupported format version {version}"))),
+        }
+    }
+
+    /// Number of values in the array.
+    pub fn count(&self) -> u64 {
+        self.shape.iter().product()
+    }
+
+    /// Checks that `len` bytes of data hold exactly the values of the array.
+    pub fn check_data(&self, len: u64) -> Result<(), ErrorKind> {
+        let expected = self.count() * self.element.size() as u64;
+        if len == expected {
+            return Ok(());
+        }
+        let shape: Vec<_> = self.shape.iter().map(u64::to_string).collect();
+        let shape = match shape.len() {
+            1 => format!("({},)", shape[0]),
+            _ => format!("({})", shape.join(", ")),
+        };
+        Err(error(format!("shape {shape} needs {expected} bytes of data, found {len}")))
+    }
+
+    /// Tells the columns of the array apart: the values along its last axis.
+    pub fn columns(&self) -> ColumnMap {
+        let columns = if self.shape.len() < 2 { 1 } else { *self.shape.last().unwrap() }.max(1);
+        ColumnMap {
+            start: self.len,
+            size: self.element.size() as u64,
+            columns,
+            rows: (self.count() / columns).max(1),
+            // A single column is stored the same way in either order.
+            fortran_order: self.fortran_order && columns > 1,
+        }
+    }
+}
+
+fn truncated(err: io::Error) -> ErrorKind {
+    match err.kind() {
+        io::ErrorKind::UnexpectedEof => error("truncated header"),
+        _ => err.into(),
+    }
+}
+
+/// The element type of a dtype string such as `<f8` or `|u1`.
+fn dtype(descr: &str) -> Option<Element> {
+    let mut chars = descr.chars();
+    let order = match chars.next()? {
+        '<' => "le",
+        '>' => "be",
+        '|' => "",
+        '=' if cfg!(target_endian = "big") => "be",
+        '=' => "le",
+        _ => return None,
+    };
+    let kind = chars.next()?;
+    let bits = chars.as_str().parse::<u32>().ok()? * 8;
+    if !matches!(kind, 'f' | 'i' | 'u') {
+        return None;
+    }
+    // Single bytes have no byte order, whatever the prefix says.
+    let order = if bits == 8 { "" } else { order };
+    Element::from_name(&format!("{kind}{bits}{order}"))
+}
+
+/// Which column the value at a byte offset of the input belongs to.
+#[derive(Clone, Copy)]
+pub struct ColumnMap {
+    start: u64,
+    size: u64,
+    columns: u64,
+    rows: u64,
+    fortran_order: bool,
+}
+
+impl ColumnMap {
+    #[inline]
+    pub fn column(&self, offset: u64) -> usize {
+        let index = (offset - self.start) / self.size;
+        let column = if self.fortran_order { index / self.rows } else { index % self.columns };
+        column as usize
+    }
+
+    /// Whether the value at `offset` is the last of its row. The rows of a
+    /// Fortran-order array are spread over the whole data, so they never end.
+    #[inline]
+    pub fn ends_row(&self, offset: u64) -> bool {
+        !self.fortran_order && self.column(offset) as u64 == self.columns - 1
+    }
+
+    /// How many bytes a row takes, if its values are stored together.
+    pub fn row_size(&self) -> Option<u64> {
+        (!self.fortran_order).then_some(self.size * self.columns)
+    }
+}
+
+/// Just enough of a Python literal parser for `.npy` header dicts.
+struct Parser<'a>(&'a str);
+
+impl<'a> Parser<'a> {
+    fn eat(&mut self, c: char) -> bool {
+        self.0 = self.0.trim_start();
+        match self.0.strip_prefix(c) {
+            Some(rest) => {
+                self.0 = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ErrorKind> {
+        if self.eat(c) {
+            return Ok(());
+        }
+        Err(error(format!("expected '{c}' in header")))
+    }
+
+    fn string(&mut self) -> Result<&'a str, ErrorKind> {
+        let quote = if self.eat('\'') { '\'' } else { self.expect('"').map(|_| '"')? };
+        let (string, rest) = self.0.split_once(quote).ok_or_else(|| error("unterminated string in header"))?;
+        self.0 = rest;
+        Ok(string)
+    }
+
+    fn boolean(&mut self) -> Result<bool, ErrorKind> {
+        self.0 = self.0.trim_start();
+        for (word, value) in [("True", true), ("False", false)] {
+            if let Some(rest) = self.0.strip_prefix(word) {
+                self.0 = rest;
+                return Ok(value);
+            }
+        }
+        Err(error("expected True or False in header"))
+    }
+
+    fn tuple(&mut self) -> Result<Vec<u64>, ErrorKind> {
+        self.expect('(')?;
+        let mut items = Vec::new();
+        while !self.eat(')') {
+            self.0 = self.0.trim_start();
+            let end = self.0.find(|c: char| !c.is_ascii_digit()).unwrap_or(self.0.len());
+            let item = self.0[..end].parse().map_err(|_| error("expected a dimension in header"))?;
+            self.0 = &self.0[end..];
+            items.push(item);
+            if !self.eat(',') {
+                self.expect(')')?;
+                break;
+            }
+        }
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ErrorKind, Header};
+    use crate::file_stat::testing::npy;
+
+    /// The file `npy` builds, with the length prefix of format `version`.
+    fn npy_version(version: u8, descr: &str, shape: &str, data: &[u8]) -> Vec<u8> {
+        let file = npy(descr, false, shape, data);
+        if version == 1 {
+            return file;
+        }
+        let dict = &file[10..];
+        let mut converted = b"\x93NUMPY".to_vec();
+        converted.extend([version, 0]);
+        converted.extend((dict.len() as u32 - data.len() as u32).to_le_bytes());
+        converted.extend(dict);
+        converted
+    }
+
+    fn message(result: Result<Header, ErrorKind>) -> String {
+        match result {
+            Err(ErrorKind::Npy(message)) => message,
+            Err(err) => panic!("not an npy error: {err}"),
+            Ok(header) => panic!("parsed, shape {:?}", header.shape),
+        }
+    }
+
+    #[test]
+    fn reads_every_format_version() {
+        let data: Vec<u8> = [1.0f64, 2.0, 3.0].iter().flat_map(|x| x.to_le_bytes()).collect();
+        for version in 1..=3 {
+            let file = npy_version(version, "<f8", "(3,)", &data);
+            let mut reader = &file[..];
+            let header = Header::read(&mut reader).unwrap();
+            assert_eq!(reader, &data[..], "version {version}");
+            for header in [header, Header::parse(&file).unwrap()] {
+                assert_eq!((header.element.to_string(), header.fortran_order), ("f64le".to_string(), false));
+                assert_eq!((header.shape, header.len), (vec![3], file.len() as u64 - 24));
+            }
+        }
+        let file = npy_version(4, "<f8", "(3,)", &data);
+        assert_eq!(message(Header::parse(&file)), "unsupported format version 4");
+    }
+
+    #[test]
+    fn reads_dtypes() {
+        let native = if cfg!(target_endian = "big") { "i16be" } else { "i16le" };
+        for (descr, element) in [("<f8", "f64le"), ("<f4", "f32le"), ("|u1", "u8"), ("<u1", "u8"), ("=i2", native), (">i4", "i32be")] {
+            let header = Header::parse(&npy(descr, false, "(3,)", &[])).unwrap();
+            assert_eq!(header.element.to_string(), element, "{descr}");
+        }
+        for descr in ["<c16", "f8", "<U3", "|b1", "<f", "<f3", "<M8[s]", ""] {
+            let err = message(Header::parse(&npy(descr, false, "(3,)", &[])));
+            assert_eq!(err, format!("unsupported dtype '{descr}'"));
+        }
+    }
+
+    #[test]
+    fn reads_shapes() {
+        for (shape, dims, count) in [("(3,)", vec![3], 3), ("()", vec![], 1), ("(2, 3)", vec![2, 3], 6), ("(4,0)", vec![4, 0], 0)] {
+            let header = Header::parse(&npy("<f8", false, shape, &[])).unwrap();
+            assert_eq!((header.count(), header.shape), (count, dims), "{shape}");
+        }
+        assert!(Header::parse(&npy("<f8", true, "(2, 3)", &[])).unwrap().fortran_order);
+        let err = message(Header::parse(&npy("<f8", false, "(-1,)", &[])));
+        assert_eq!(err, "expected a dimension in header");
+    }
+
+    #[test]
+    fn rejects_other_keys_and_missing_ones() {
+        let mut file = npy("<f8", false, "(3,)", &[]);
+        let at = file.iter().position(|&b| b == b'}').unwrap();
+        file.splice(at..at, *b"'extra': 1");
+        assert_eq!(message(Header::parse(&file)), "unexpected key 'extra' in header");
+
+        for (dict, err) in [
+            ("{'fortran_order': False, 'shape': (3,)}", "header has no 'descr'"),
+            ("{'descr': '<f8', 'shape': (3,)}", "header has no 'fortran_order'"),
+            ("{'descr': '<f8', 'fortran_order': False}", "header has no 'shape'"),
+            ("{'descr': '<f8', 'fortran_order': 0, 'shape': (3,)}", "expected True or False in header"),
+            ("{'descr': '<f8' 'shape': (3,)}", "expected '}' in header"),
+            ("{'descr': '<f8", "unterminated string in header"),
+        ] {
+            let mut file = b"\x93NUMPY\x01\x00".to_vec();
+            file.extend((dict.len() as u16).to_le_bytes());
+            file.extend(dict.as_bytes());
+            assert_eq!(message(Header::parse(&file)), err, "{dict}");
+        }
+        assert_eq!(message(Header::parse(b"\x93NUMPX\x01\x00\x00\x00")), "not a .npy file");
+    }
+
+    #[test]
+    fn rejects_truncated_headers() {
+        for version in 1..=3 {
+            let file = npy_version(version, "<f8", "(3,)", &[]);
+            for cut in 0..file.len() {
+                let err = message(Header::read(&file[..cut]));
+                assert_eq!(err, "truncated header", "version {version}, {cut} bytes");
+                let err = message(Header::parse(&file[..cut]));
+                let expected = if cut < 10 { "not a .npy file" } else { "truncated header" };
+                assert_eq!(err, expected, "version {version}, {cut} bytes");
+            }
+        }
+    }
+
+    #[test]
+    fn checks_data_length() {
+        for (descr, shape, len, err) in [
+            ("<f8", "(3,)", 16, Some("shape (3,) needs 24 bytes of data, found 16")),
+            ("<f8", "(3,)", 24, None),
+            ("<f8", "(3,)", 32, Some("shape (3,) needs 24 bytes of data, found 32")),
+            ("|u1", "(2, 3)", 6, None),
+            ("<i4", "(2, 3)", 12, Some("shape (2, 3) needs 24 bytes of data, found 12")),
+            ("<f4", "()", 4, None),
+            ("<f4", "()", 0, Some("shape () needs 4 bytes of data, found 0")),
+            ("<f8", "(0, 5)", 0, None),
+        ] {
+            let header = Header::parse(&npy(descr, false, shape, &[])).unwrap();
+            let result = header.check_data(len).map_err(|err| match err {
+                ErrorKind::Npy(message) => message,
+                err => panic!("not an npy error: {err}"),
+            });
+            assert_eq!(result.err().as_deref(), err, "{descr} {shape}");
+        }
+    }
+
+    #[test]
+    fn maps_offsets_to_columns_in_either_order() {
+        // (2, 3) of u2: the offsets of the six values, after the header.
+        for (fortran_order, columns, ends, row_size) in [
+            (false, [0, 1, 2, 0, 1, 2], [false, false, true, false, false, true], Some(6)),
+            (true, [0, 0, 1, 1, 2, 2], [false; 6], None),
+        ] {
+            let header = Header::parse(&npy("<u2", fortran_order, "(2, 3)", &[])).unwrap();
+            let map = header.columns();
+            let offsets: Vec<u64> = (0..6).map(|i| header.len + 2 * i).collect();
+            assert_eq!(offsets.iter().map(|&at| map.column(at)).collect::<Vec<_>>(), columns);
+            assert_eq!(offsets.iter().map(|&at| map.ends_row(at)).collect::<Vec<_>>(), ends);
+            assert_eq!(map.row_size(), row_size);
+        }
+        // A single column is laid out the same way in either order.
+        for shape in ["(4,)", "(4, 1)"] {
+            let header = Header::parse(&npy("<f8", true, shape, &[])).unwrap();
+            let map = header.columns();
+            assert_eq!(map.row_size(), Some(8), "{shape}");
+            assert!((0..4).all(|i| map.column(header.len + 8 * i) == 0 && map.ends_row(header.len + 8 * i)));
+        }
+    }
+}
@@ -0,0 +1,921 @@
+use std::thread;
+
+use super::csv::{self, Record};
+use super::npy::{ColumnMap, Header};
+use super::{
+    float, jsonl, tokenize, Accumulator, Element, ErrorKind, Format, Input, Location, NonFinite, OnError, ReadOptions, Result,
+    Skipped, Source,
+};
+
+/// Chunks smaller than this are not worth a thread of their own.
+const MIN_CHUNK: usize = 1 << 20;
+
+/// Where warnings about skipped values go.
+enum Warnings<'a> {
+    Off,
+    /// Straight to [`ReadOptions::on_warning`], for the input named here.
+    Pass(&'a str),
+    /// Kept until the chunk is merged and the value indices are known.
+    Collect(Vec<ErrorKind>),
+}
+
+/// How the values of one input are laid out, once its header, if any, is read.
+#[derive(Clone, Copy)]
+enum Layout {
+    Text,
+    Binary {
+        element: Element,
+        /// Where the values start.
+        start: u64,
+        /// How to tell the columns apart, when they are summarized separately.
+        columns: Option<ColumnMap>,
+    },
+    Delimited {
+        delimiter: u8,
+    },
+    Jsonl,
+}
+
+impl Layout {
+    fn new(options: &ReadOptions, header: Option<&Header>) -> Self {
+        match (options.format, header) {
+            (_, Some(header)) => Layout::Binary {
+                element: header.element,
+                start: header.len,
+                columns: options.per_column.then(|| header.columns()),
+            },
+            (Format::Binary(element), None) => Layout::Binary { element, start: 0, columns: None },
+            (Format::Delimited { delimiter }, None) => Layout::Delimited { delimiter },
+            (Format::Jsonl, None) => Layout::Jsonl,
+            (Format::Text | Format::Npy, None) => Layout::Text,
+        }
+    }
+
+    /// Whether the values of a row are spread over the data, so rows never end.
+    fn scatters_rows(&self) -> bool {
+        matches!(self, Layout::Binary { columns: Some(columns), .. } if columns.row_size().is_none())
+    }
+}
+
+/// Turns tokens into values, applying the bad-value policies.
+struct Values<'a> {
+    options: &'a ReadOptions,
+    layout: Layout,
+    skipped: Skipped,
+    /// Index of the next token, counted from the start of the chunk.
+    index: u64,
+    warnings: Warnings<'a>,
+    /// The names in the header of a delimited input, if it has one, once it is
+    /// known whether it has one.
+    header: Option<Option<Vec<String>>>,
+    /// The first record of a delimited input, while it is not yet known
+    /// whether it is a header: until the second has been seen.
+    first: Option<csv::RecordBuf>,
+    /// The field of a delimited record that each column comes from, once the
+    /// first data record has been seen.
+    fields: Option<Vec<usize>>,
+    /// Names of the columns of a delimited or JSON Lines input.
+    names: Vec<String>,
+}
+
+impl<'a> Values<'a> {
+    fn new(options: &'a ReadOptions, layout: Layout, warnings: Warnings<'a>) -> Self {
+        Values {
+            options,
+            layout,
+            skipped: Skipped::default(),
+            index: 0,
+            warnings,
+            header: None,
+            first: None,
+            fields: None,
+            names: match layout {
+                Layout::Jsonl => options.fields.clone(),
+                Layout::Text | Layout::Binary { .. } | Layout::Delimited { .. } => Vec::new(),
+            },
+        }
+    }
+
+    fn next(&mut self, offset: u64) -> Location {
+        let location = Location { offset, index: self.index };
+        self.index += 1;
+        location
+    }
+
+    /// The value of `token`, or `None` when it is skipped.
+    fn parse(&mut self, token: &[u8], offset: u64) -> std::result::Result<Option<f64>, ErrorKind> {
+        let location = self.next(offset);
+        let value = float::parse(token).map_or_else(
+            || {
+                std::str::from_utf8(token)
+                    .map_err(|_| ErrorKind::utf8(location, token))
+                    .and_then(|text| text.parse::<f64>().map_err(|err| ErrorKind::parse(location, token, err)))
+            },
+            Ok,
+        );
+        match value {
+            Ok(x) => self.check(x, location, token),
+            Err(err) => self.skip(err, None),
+        }
+    }
+
+    /// `x` as read from binary input, or `None` when it is skipped.
+    fn decode(&mut self, x: f64, offset: u64) -> std::result::Result<Option<f64>, ErrorKind> {
+        let location = self.next(offset);
+        if x.is_finite() {
+            return Ok(Some(x));
+        }
+        self.check(x, location, x.to_string().as_bytes())
+    }
+
+    fn check(&mut self, x: f64, location: Location, token: &[u8]) -> std::result::Result<Option<f64>, ErrorKind> {
+        match self.options.non_finite {
+            _ if x.is_finite() => Ok(Some(x)),
+            NonFinite::Include => Ok(Some(x)),
+            NonFinite::Exclude => {
+                self.skipped.count(Some(x));
+                Ok(None)
+            }
+            NonFinite::Error => self.skip(ErrorKind::non_finite(location, token), Some(x)),
+        }
+    }
+
+    /// Fails with `err`, or skips the value it is about, depending on the policy.
+    fn skip(&mut self, err: ErrorKind, value: Option<f64>) -> std::result::Result<Option<f64>, ErrorKind> {
+        if self.options.on_error == OnError::Fail {
+            return Err(err);
+        }
+        match &mut self.warnings {
+            Warnings::Off => {}
+            Warnings::Pass(path) => warn(self.options, err, path),
+            Warnings::Collect(warnings) => warnings.push(err),
+        }
+        self.skipped.count(value);
+        Ok(None)
+    }
+
+    /// Feeds the selected fields of a delimited `record` to `acc`, each to its
+    /// own column, as a row. The first record may be a header instead; when
+    /// that is to be guessed, it waits for the second to be compared with.
+    fn record<A: Accumulator>(&mut self, record: &Record, acc: &mut A) -> std::result::Result<(), ErrorKind> {
+        if self.header.is_some() {
+            return self.data_record(record, acc);
+        }
+        let by_name = self.options.columns.iter().any(|column| matches!(column, csv::Column::Name(_)));
+        match (self.options.header, self.first.take()) {
+            (Some(header), _) => self.first_record(record, header, acc),
+            (None, _) if by_name => self.first_record(record, true, acc),
+            (None, None) => {
+                self.first = Some(record.to_buf());
+                Ok(())
+            }
+            (None, Some(first)) => {
+                let header = csv::is_header(&first.record(), Some(record), is_number);
+                self.first_record(&first.record(), header, acc)?;
+                self.data_record(record, acc)
+            }
+        }
+    }
+
+    /// Feeds the first record of a delimited input to `acc` if it turns out
+    /// not to be a header, which is guessed at the end if it is the only one.
+    fn end_records<A: Accumulator>(&mut self, acc: &mut A) -> std::result::Result<(), ErrorKind> {
+        match self.first.take() {
+            Some(first) => {
+                let header = csv::is_header(&first.record(), None, is_number);
+                self.first_record(&first.record(), header, acc)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Takes the `first` record of a delimited input as its header, or else
+    /// feeds it to `acc`.
+    fn first_record<A: Accumulator>(&mut self, first: &Record, header: bool, acc: &mut A) -> std::result::Result<(), ErrorKind> {
+        self.header = Some(header.then(|| csv::names(first)));
+        if header {
+            Ok(())
+        } else {
+            self.data_record(first, acc)
+        }
+    }
+
+    /// Feeds the selected fields of a delimited `record` that holds data to
+    /// `acc`. The first one picks the fields, unless columns were asked for.
+    fn data_record<A: Accumulator>(&mut self, record: &Record, acc: &mut A) -> std::result::Result<(), ErrorKind> {
+        let fields = match self.fields.take() {
+            Some(fields) => fields,
+            None => {
+                let columns = &self.options.columns;
+                let header = self.header.as_ref().and_then(|header| header.as_deref());
+                let (fields, names) = if columns.is_empty() {
+                    csv::numeric(record, header, is_number)
+                } else {
+                    csv::resolve(columns, header)?
+                };
+                if fields.is_empty() {
+                    return Err(ErrorKind::NoColumn("no numeric columns in the first record".to_string()));
+                }
+                self.names = names;
+                fields
+            }
+        };
+
+        let mut result = Ok(());
+        for (column, &i) in fields.iter().enumerate() {
+            let value = match record.field(i) {
+                Some(field) => self.parse(field.trim_ascii(), record.field_offset(i)),
+                None => {
+                    let location = self.next(record.offset);
+                    self.skip(ErrorKind::missing(location, &self.names[column]), None)
+                }
+            };
+            match value {
+                Ok(Some(x)) => acc.push_column(column, x),
+                Ok(None) => {}
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+        }
+        acc.end_row();
+        self.fields = Some(fields);
+        result
+    }
+
+    /// Feeds the fields of a JSON Lines `line`, which starts at `offset`, to
+    /// `acc`, each to its own column, as a row.
+    fn json<A: Accumulator>(&mut self, line: &[u8], offset: u64, acc: &mut A) -> std::result::Result<(), ErrorKind> {
+        let options = self.options;
+        let mut result = Ok(());
+        for (column, path) in options.fields.iter().enumerate() {
+            let value = match jsonl::field(line, path) {
+                Ok(Some((start, token))) => self.parse(token, offset + start as u64),
+                Ok(None) => {
+                    let location = self.next(offset);
+                    self.skip(ErrorKind::missing(location, path), None)
+                }
+                Err(message) => {
+                    let location = self.next(offset);
+                    self.skip(ErrorKind::json(location, message), None)
+                }
+            };
+            match value {
+                Ok(Some(x)) => acc.push_column(column, x),
+                Ok(None) => {}
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+        }
+        acc.end_row();
+        result
+    }
+
+    /// Feeds a value of binary input to `acc`, in its column if there are any.
+    fn binary<A: Accumulator>(
+        &mut self,
+        x: f64,
+        offset: u64,
+        columns: Option<ColumnMap>,
+        acc: &mut A,
+    ) -> std::result::Result<(), ErrorKind> {
+        let x = self.decode(x, offset)?;
+        match columns {
+            None => x.map_or((), |x| acc.push(x)),
+            Some(columns) => {
+                if let Some(x) = x {
+                    acc.push_column(columns.column(offset), x);
+                }
+                if columns.ends_row(offset) {
+                    acc.end_row();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Feeds every value of in-memory `data`, which starts at `offset` in the input, to `acc`.
+    fn read_in<A: Accumulator>(&mut self, data: &[u8], offset: u64, acc: &mut A) -> std::result::Result<(), ErrorKind> {
+        match self.layout {
+            Layout::Text => tokenize::for_each_token_in(data, offset, &self.options.separators, |token, offset| {
+                if let Some(x) = self.parse(token, offset)? {
+                    acc.push(x);
+                }
+                Ok(())
+            }),
+            Layout::Binary { element, columns, .. } => element.for_each_in(data, offset, |x, offset| {
+                self.binary(x, offset, columns, acc)
+            }),
+            Layout::Delimited { delimiter } => {
+                csv::for_each_record_in(data, delimiter, |record| self.record(record, acc))?;
+                self.end_records(acc)
+            }
+            Layout::Jsonl => jsonl::for_each_line_in(data, offset, |line, offset| self.json(line, offset, acc)),
+        }
+    }
+
+    /// Feeds every value of `reader`, which is at `layout`'s start, to `acc`.
+    fn read<A: Accumulator>(&mut self, reader: impl std::io::Read, acc: &mut A) -> std::result::Result<(), ErrorKind> {
+        match self.layout {
+            Layout::Text => tokenize::for_each_token(reader, &self.options.separators, |token, offset| {
+                if let Some(x) = self.parse(token, offset)? {
+                    acc.push(x);
+                }
+                Ok(())
+            }),
+            Layout::Binary { element, start, columns } => element.for_each(reader, |x, offset| {
+                self.binary(x, start + offset, columns, acc)
+            }),
+            Layout::Delimited { delimiter } => {
+                csv::for_each_record(reader, delimiter, |record| self.record(record, acc))?;
+                self.end_records(acc)
+            }
+            Layout::Jsonl => jsonl::for_each_line(reader, |line, offset| self.json(line, offset, acc)),
+        }
+    }
+}
+
+/// Whether a field of what may be a header is a number, or empty.
+fn is_number(field: &[u8]) -> bool {
+    let field = field.trim_ascii();
+    field.is_empty()
+        || float::parse(field).is_some()
+        || std::str::from_utf8(field).is_ok_and(|text| text.parse::<f64>().is_ok())
+}
+
+fn warn(options: &ReadOptions, err: ErrorKind, path: &str) {
+    if let Some(on_warning) = &options.on_warning {
+        on_warning.call(&err.at(path));
+    }
+}
+
+fn threads(options: &ReadOptions) -> usize {
+    match options.threads {
+        0 => thread::available_parallelism().map_or(1, usize::from),
+        n => n,
+    }
+}
+
+/// Reads the input once, feeding every value to `acc`.
+///
+/// A mapped input is cut into chunks at separators and each chunk is scanned
+/// on its own thread by a clone of `acc`, so `acc` should not have seen any
+/// values yet. The chunks are merged back in input order, which gives the same
+/// result as a single thread for every order-sensitive statistic.
+pub fn scan<A: Accumulator + Clone + Send>(source: &mut Source, mut acc: A) -> Result<(A::Output, Skipped)> {
+    let options = source.options().clone();
+    let input = source.open().map_err(|err| ErrorKind::from(err).at(source.name()))?;
+    let name = source.name().to_string();
+    let path = name.as_str();
+    let warn_skipped = options.on_error == OnError::Warn && options.on_warning.is_some() && source.passes() == 1;
+
+    let mut values = match input {
+        Input::Mapped(map) => {
+            let header = match options.format {
+                Format::Npy => Some(Header::parse(&map).map_err(|kind| kind.at(path))?),
+                Format::Text | Format::Binary(_) | Format::Delimited { .. } | Format::Jsonl => None,
+            };
+            let layout = Layout::new(&options, header.as_ref());
+            source.set_scattered_rows(layout.scatters_rows());
+            let start = match layout {
+                Layout::Text | Layout::Delimited { .. } | Layout::Jsonl => 0,
+                Layout::Binary { element, start, .. } => {
+                    let len = map.len() as u64 - start;
+                    match &header {
+                        Some(header) => header.check_data(len),
+                        None => element.check_len(len),
+                    }
+                    .map_err(|kind| kind.at(path))?;
+                    start
+                }
+            };
+            let data = &map[start as usize..];
+
+            let chunks = match layout {
+                // A quoted field can hold a line break, so records cannot be
+                // told apart starting from the middle of the input.
+                Layout::Delimited { .. } => 1,
+                Layout::Text | Layout::Binary { .. } | Layout::Jsonl => threads(&options).min(data.len() / MIN_CHUNK),
+            };
+            if chunks > 1 {
+                let (skipped, names) = scan_chunks(data, start, chunks, &options, layout, warn_skipped, path, &mut acc)?;
+                source.set_column_names(names);
+                return Ok((acc.finish(), skipped));
+            }
+            let mut values = Values::new(&options, layout, if warn_skipped { Warnings::Pass(path) } else { Warnings::Off });
+            values.read_in(data, start, &mut acc).map_err(|kind| kind.at(path))?;
+            values
+        }
+        Input::Stream(mut reader) => {
+            let header = match options.format {
+                Format::Npy => Some(Header::read(&mut reader).map_err(|kind| kind.at(path))?),
+                Format::Text | Format::Binary(_) | Format::Delimited { .. } | Format::Jsonl => None,
+            };
+            let layout = Layout::new(&options, header.as_ref());
+            source.set_scattered_rows(layout.scatters_rows());
+            let mut values = Values::new(&options, layout, if warn_skipped { Warnings::Pass(path) } else { Warnings::Off });
+            values.read(reader, &mut acc).map_err(|kind| kind.at(path))?;
+            if let Some(header) = header {
+                let size = header.element.size() as u64;
+                header.check_data(values.index * size).map_err(|kind| kind.at(path))?;
+            }
+            values
+        }
+    };
+
+    let skipped = values.skipped;
+    let names = std::mem::take(&mut values.names);
+    drop(values);
+    source.set_column_names(names);
+    Ok((acc.finish(), skipped))
+}
+
+/// Where each of `chunks` chunks of `data` starts, plus the end of `data`.
+/// Every chunk but the first starts at a separator or at an element boundary,
+/// so no value is cut.
+fn chunk_bounds(data: &[u8], chunks: usize, options: &ReadOptions, layout: Layout) -> Vec<usize> {
+    let mut bounds = vec![0];
+    for i in 1..chunks {
+        let mut bound = (data.len() / chunks * i).max(bounds[i - 1]);
+        match layout {
+            Layout::Text => {
+                while bound < data.len() && !options.separators.contains(data[bound]) {
+                    bound += 1;
+                }
+            }
+            // A JSON string cannot hold a raw line break, so every one ends a line.
+            Layout::Jsonl => {
+                while bound < data.len() && data[bound] != b'\n' {
+                    bound += 1;
+                }
+            }
+            // Rows stay whole, for statistics across columns.
+            Layout::Binary { element, columns, .. } => {
+                let row_size = columns.and_then(|columns| columns.row_size()).unwrap_or(element.size() as u64);
+                bound -= bound % row_size as usize;
+            }
+            Layout::Delimited { .. } => unreachable!("delimited input is scanned on one thread"),
+        }
+        bounds.push(bound);
+    }
+    bounds.push(data.len());
+    bounds
+}
+
+/// Scans `data`, which starts at `offset` in the input, in `chunks` chunks on
+/// as many threads. Returns what was skipped and the names of the columns.
+#[allow(clippy::too_many_arguments)]
+fn scan_chunks<A: Accumulator + Clone + Send>(
+    data: &[u8],
+    offset: u64,
+    chunks: usize,
+    options: &ReadOptions,
+    layout: Layout,
+    warn_skipped: bool,
+    path: &str,
+    acc: &mut A,
+) -> Result<(Skipped, Vec<String>)> {
+    let bounds = chunk_bounds(data, chunks, options, layout);
+    let results: Vec<_> = thread::scope(|scope| {
+        let workers: Vec<_> = bounds.windows(2)
+            .map(|range| {
+                let (start, end) = (range[0], range[1]);
+                let mut chunk_acc = acc.clone();
+                scope.spawn(move || {
+                    let warnings = if warn_skipped { Warnings::Collect(Vec::new()) } else { Warnings::Off };
+                    let mut values = Values::new(options, layout, warnings);
+                    let result = values.read_in(&data[start..end], offset + start as u64, &mut chunk_acc);
+                    (chunk_acc, values.skipped, values.index, values.warnings, values.names, result)
+                })
+            })
+            .collect();
+        workers.into_iter()
+            .map(|worker| worker.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+            .collect()
+    });
+
+    let mut skipped = Skipped::default();
+    let mut names = Vec::new();
+    // Value indices within a chunk become input-wide once the chunks before it are counted.
+    let mut index = 0;
+    for (chunk_acc, chunk_skipped, chunk_len, warnings, chunk_names, result) in results {
+        if let Err(mut err) = result {
+            err.shift_index(index);
+            return Err(err.at(path));
+        }
+        if let Warnings::Collect(warnings) = warnings {
+            for mut err in warnings {
+                err.shift_index(index);
+                warn(options, err, path);
+            }
+        }
+        acc.merge(chunk_acc);
+        skipped.merge(chunk_skipped);
+        index += chunk_len;
+        names = chunk_names;
+    }
+    Ok((skipped, names))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chunk_bounds, scan, scan_chunks, Layout, Values, Warnings, MIN_CHUNK};
+    use crate::file_stat::testing::{Rng, TestFile, Trickle};
+    use crate::file_stat::{
+        Accumulator, Column, Columns, Comoments, Element, ErrorKind, Format, Histogram, Kll, Len, MinMax, Moments,
+        NonFinite, OnError, OnWarning, ReadOptions, Separators, Tails,
+    };
+    use std::sync::{Arc, Mutex};
+
+    type Everything = (Len, MinMax, Moments, Tails, Histogram, Kll);
+
+    fn everything() -> Everything {
+        (Len::default(), MinMax::default(), Moments::default(), Tails::new(7), Histogram::default(), Kll::new(200))
+    }
+
+    /// Scans `data` on one thread, without chunks.
+    fn sequential<A: Accumulator>(data: &[u8], options: &ReadOptions, layout: Layout, mut acc: A) -> A::Output {
+        let mut values = Values::new(options, layout, Warnings::Off);
+        values.read_in(data, 0, &mut acc).unwrap();
+        acc.finish()
+    }
+
+    fn chunked<A: Accumulator + Clone + Send>(
+        data: &[u8],
+        chunks: usize,
+        options: &ReadOptions,
+        layout: Layout,
+        mut acc: A,
+    ) -> A::Output {
+        scan_chunks(data, 0, chunks, options, layout, false, "test", &mut acc).unwrap();
+        acc.finish()
+    }
+
+    /// Checks the chunked result of [`everything`] against the sequential one:
+    /// exactly, but for sums that round differently and the sketch, which
+    /// compacts differently.
+    fn assert_same(
+        chunked: <Everything as Accumulator>::Output,
+        sequential: <Everything as Accumulator>::Output,
+        values: &[f64],
+    ) {
+        let (len, min_max, moments, tails, histogram, sketch) = chunked;
+        assert_eq!(len, sequential.0);
+        assert_eq!(min_max, sequential.1);
+        let close = |a: f64, b: f64| (a - b).abs() <= 1e-9 * a.abs().max(b.abs()).max(1.0);
+        assert!(close(moments.mean(), sequential.2.mean()), "{} != {}", moments.mean(), sequential.2.mean());
+        assert!(close(moments.variance(), sequential.2.variance()));
+        assert!(close(moments.kurtosis(), sequential.2.kurtosis()));
+        assert_eq!(tails, sequential.3);
+        assert!(histogram == sequential.4);
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let rank = |x: f64| sorted.partition_point(|&y| y < x) as f64 / sorted.len() as f64;
+        for q in [0.1, 0.5, 0.9] {
+            let x = sketch.quantile(q).unwrap();
+            assert!((rank(x) - q).abs() <= sketch.rank_error(), "q {q}: {x} has rank {}", rank(x));
+        }
+    }
+
+    /// Text of `values` between runs of mixed separators, some of them long.
+    fn text(values: &[f64], rng: &mut Rng) -> Vec<u8> {
+        let mut data = Vec::new();
+        for x in values {
+            data.extend(format!("{x:?}").bytes());
+            let separators = [&b" "[..], b"\n", b",", b"\t\r\n", b";;  ,\n\n"];
+            data.extend(separators[rng.below(separators.len() as u64) as usize]);
+        }
+        data
+    }
+
+    #[test]
+    fn text_chunks_start_at_separators() {
+        let options = ReadOptions { separators: Separators::new(b",;"), ..ReadOptions::default() };
+        let mut rng = Rng(8);
+        let data = text(&rng.values(500), &mut rng);
+        for chunks in [1, 2, 3, 7, 64, 1_000, data.len()] {
+            let bounds = chunk_bounds(&data, chunks, &options, Layout::Text);
+            assert_eq!((bounds[0], bounds[chunks]), (0, data.len()));
+            for pair in bounds.windows(2) {
+                assert!(pair[0] <= pair[1]);
+            }
+            for &bound in &bounds[1..chunks] {
+                assert!(bound == data.len() || options.separators.contains(data[bound]), "{chunks} chunks cut at {bound}");
+            }
+        }
+    }
+
+    #[test]
+    fn chunked_text_matches_one_thread() {
+        let options = ReadOptions { separators: Separators::new(b",;"), ..ReadOptions::default() };
+        let mut rng = Rng(9);
+        let values = rng.values(5_000);
+        let data = text(&values, &mut rng);
+        assert_eq!(sequential(&data, &options, Layout::Text, Len::default()), values.len());
+        // Most of these cut through the middle of a token before moving to a separator.
+        for chunks in [2, 3, 5, 16, 97, 1_000] {
+            let expected = sequential(&data, &options, Layout::Text, everything());
+            assert_same(chunked(&data, chunks, &options, Layout::Text, everything()), expected, &values);
+        }
+        // A token longer than a chunk leaves some chunks empty.
+        let mut long = data.clone();
+        long.splice(0..0, format!("{}1 ", "0".repeat(5_000)).bytes());
+        let values: Vec<f64> = [1.0].iter().chain(&values).copied().collect();
+        let expected = sequential(&long, &options, Layout::Text, everything());
+        assert_same(chunked(&long, 64, &options, Layout::Text, everything()), expected, &values);
+    }
+
+    #[test]
+    fn chunked_binary_matches_one_thread() {
+        let element = Element::from_name("f64le").unwrap();
+        let options = ReadOptions { format: Format::Binary(element), ..ReadOptions::default() };
+        let layout = Layout::Binary { element, start: 0, columns: None };
+        let values = Rng(10).values(3_001);
+        let data: Vec<u8> = values.iter().flat_map(|x| x.to_le_bytes()).collect();
+        for chunks in [2, 3, 7, 100] {
+            let bounds = chunk_bounds(&data, chunks, &options, layout);
+            assert!(bounds.iter().all(|bound| bound % 8 == 0));
+            let expected = sequential(&data, &options, layout, everything());
+            assert_same(chunked(&data, chunks, &options, layout, everything()), expected, &values);
+        }
+    }
+
+    #[test]
+    fn chunked_jsonl_keeps_rows_whole() {
+        let options = ReadOptions {
+            format: Format::Jsonl,
+            fields: vec!["a".to_string(), "b.c".to_string()],
+            on_error: OnError::Skip,
+            ..ReadOptions::default()
+        };
+        let mut rng = Rng(11);
+        let mut data = String::new();
+        for i in 0..3_000 {
+            match rng.below(20) {
+                0 => data.push_str("{\"a\": 1}\n"),
+                1 => data.push('\n'),
+                _ => data.push_str(&format!("{{\"b\": {{\"c\": {}}}, \"x\": \"}}\\n\", \"a\": {i}}}\n", rng.float())),
+            }
+        }
+        let acc = || (Columns::new((Len::default(), Tails::new(3))), Comoments::default());
+        let (columns, comoments) = sequential(data.as_bytes(), &options, Layout::Jsonl, acc());
+        for chunks in [2, 5, 33, 500] {
+            let bounds = chunk_bounds(data.as_bytes(), chunks, &options, Layout::Jsonl);
+            assert!(bounds[1..chunks].iter().all(|&bound| bound == data.len() || data.as_bytes()[bound] == b'\n'));
+            let (chunked_columns, chunked_comoments) = chunked(data.as_bytes(), chunks, &options, Layout::Jsonl, acc());
+            assert_eq!(chunked_columns.len(), columns.len());
+            for (a, b) in chunked_columns.iter().zip(&columns) {
+                assert_eq!(a.0, b.0);
+                assert_eq!(a.1, b.1);
+            }
+            let (a, b) = (chunked_comoments.covariance(0, 1), comoments.covariance(0, 1));
+            assert!((a - b).abs() <= 1e-9 * a.abs().max(1.0), "{a} != {b}");
+        }
+    }
+
+    /// The names and values of the columns of delimited `data`, read both in
+    /// memory and in small pieces.
+    fn delimited(data: &str, header: Option<bool>, columns: Vec<Column>) -> (Vec<String>, Vec<Vec<f64>>) {
+        let options = ReadOptions { format: Format::Delimited { delimiter: b',' }, header, columns, ..ReadOptions::default() };
+        let layout = Layout::Delimited { delimiter: b',' };
+        let read = |in_memory: bool| {
+            let mut values = Values::new(&options, layout, Warnings::Off);
+            let mut acc = Columns::new(Tails::new(10));
+            if in_memory {
+                values.read_in(data.as_bytes(), 0, &mut acc).unwrap();
+            } else {
+                values.read(Trickle::new(data.as_bytes(), 2), &mut acc).unwrap();
+            }
+            (values.names, acc.finish().into_iter().map(|(smallest, _)| smallest).collect::<Vec<_>>())
+        };
+        let read_in = read(true);
+        assert_eq!(read(false), read_in);
+        read_in
+    }
+
+    #[test]
+    fn delimited_headers_are_guessed_from_the_second_record() {
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+
+        // A column of labels is no header.
+        let (columns, values) = delimited("foo,1,2\nbar,3,4\nbaz,5,6\n", None, Vec::new());
+        assert_eq!(columns, names(&["column 2", "column 3"]));
+        assert_eq!(values, [vec![1.0, 3.0, 5.0], vec![2.0, 4.0, 6.0]]);
+
+        let (columns, values) = delimited("label,a,b\r\nfoo,1,2\r\nbar,3,\"4\"\r\n", None, Vec::new());
+        assert_eq!(columns, names(&["a", "b"]));
+        assert_eq!(values, [vec![1.0, 3.0], vec![2.0, 4.0]]);
+
+        let (columns, values) = delimited("1,2\n3,4\n", None, Vec::new());
+        assert_eq!(columns, names(&["column 1", "column 2"]));
+        assert_eq!(values, [vec![1.0, 3.0], vec![2.0, 4.0]]);
+
+        // With a single record, it is a header if it holds no number.
+        assert_eq!(delimited("x,1", None, Vec::new()), (names(&["column 2"]), vec![vec![1.0]]));
+        let (_, values) = delimited("a,b\n", None, vec![Column::Index(1)]);
+        assert!(values.iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn delimited_headers_can_be_forced() {
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        let (columns, values) = delimited("1,2\n3,4\n", Some(true), Vec::new());
+        assert_eq!(columns, names(&["1", "2"]));
+        assert_eq!(values, [vec![3.0], vec![4.0]]);
+
+        let (columns, values) = delimited("a,1\nb,2\n", Some(false), vec![Column::Index(1)]);
+        assert_eq!(columns, names(&["column 2"]));
+        assert_eq!(values, [vec![1.0, 2.0]]);
+
+        // Columns asked for by name need a header.
+        let (columns, values) = delimited("a,1\nb,2\n3,4\n", None, vec![Column::Name("1".to_string())]);
+        assert_eq!(columns, names(&["1"]));
+        assert_eq!(values, [vec![2.0, 4.0]]);
+    }
+
+    /// Every value pushed, in order, NaN included.
+    #[derive(Default)]
+    struct Pushed(Vec<f64>);
+
+    impl Accumulator for Pushed {
+        type Output = Vec<String>;
+
+        fn push(&mut self, x: f64) {
+            self.0.push(x);
+        }
+
+        fn merge(&mut self, other: Self) {
+            self.0.extend(other.0);
+        }
+
+        fn finish(self) -> Vec<String> {
+            self.0.iter().map(f64::to_string).collect()
+        }
+    }
+
+    #[test]
+    fn non_finite_values_are_taken_left_out_or_skipped() {
+        let data = b"1 NaN inf -inf N/A 2";
+        let invalid = "invalid number 'N/A' (value 4 at byte 15): invalid float literal";
+        for (non_finite, expected, nan, infinite, warnings) in [
+            (NonFinite::Include, &["1", "NaN", "inf", "-inf", "2"][..], 0, 0, &[invalid][..]),
+            (NonFinite::Exclude, &["1", "2"], 1, 2, &[invalid]),
+            (
+                NonFinite::Error,
+                &["1", "2"],
+                1,
+                2,
+                &[
+                    "non-finite value 'NaN' (value 1 at byte 2)",
+                    "non-finite value 'inf' (value 2 at byte 6)",
+                    "non-finite value '-inf' (value 3 at byte 10)",
+                    invalid,
+                ],
+            ),
+        ] {
+            let options = ReadOptions { non_finite, on_error: OnError::Skip, ..ReadOptions::default() };
+            for in_memory in [true, false] {
+                let mut values = Values::new(&options, Layout::Text, Warnings::Collect(Vec::new()));
+                let mut acc = Pushed::default();
+                if in_memory {
+                    values.read_in(data, 0, &mut acc).unwrap();
+                } else {
+                    values.read(Trickle::new(data, 3), &mut acc).unwrap();
+                }
+                assert_eq!(acc.finish(), expected, "{non_finite:?}");
+                let skipped = values.skipped;
+                assert_eq!((skipped.invalid, skipped.nan, skipped.infinite), (1, nan, infinite), "{non_finite:?}");
+                let Warnings::Collect(collected) = values.warnings else { unreachable!() };
+                assert_eq!(collected.iter().map(ErrorKind::to_string).collect::<Vec<_>>(), warnings, "{non_finite:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn json_fields_that_are_missing_or_not_numbers_are_skipped() {
+        let options = ReadOptions {
+            format: Format::Jsonl,
+            fields: vec!["a".to_string(), "b.c".to_string()],
+            on_error: OnError::Warn,
+            ..ReadOptions::default()
+        };
+        let data = concat!(
+            "{\"a\": 1, \"b\": {\"c\": 2}}\n",
+            "{\"a\": \"x\", \"b\": {}}\n",
+            "\n",
+            "{\"b\": {\"c\": \"3\"}}\r\n",
+            "[4, 5]\n",
+            "{\"a\": 6 \"b\": 7}\n",
+            "{\"a\": 8, \"b\": {\"c\": true}}",
+        );
+        let read = |in_memory: bool| {
+            let mut values = Values::new(&options, Layout::Jsonl, Warnings::Collect(Vec::new()));
+            let mut acc = Columns::new(Tails::new(5));
+            if in_memory {
+                values.read_in(data.as_bytes(), 0, &mut acc).unwrap();
+            } else {
+                values.read(Trickle::new(data.as_bytes(), 3), &mut acc).unwrap();
+            }
+            let Warnings::Collect(warnings) = values.warnings else { unreachable!() };
+            (acc.finish(), values.skipped, warnings)
+        };
+
+        let (columns, skipped, warnings) = read(true);
+        let sorted: Vec<Vec<f64>> = columns.iter().map(|(smallest, _)| smallest.clone()).collect();
+        // Quoted numbers are numbers.
+        assert_eq!(sorted, [vec![1.0, 6.0, 8.0], vec![2.0, 3.0]]);
+        assert_eq!(skipped.invalid, 7);
+        let warnings: Vec<String> = warnings.iter().map(ErrorKind::to_string).collect();
+        assert_eq!(
+            warnings,
+            [
+                "invalid number 'x' (value 2 at byte 31): invalid float literal",
+                "missing field 'b.c' (value 3 at byte 24)",
+                "missing field 'a' (value 4 at byte 45)",
+                // An array has no fields.
+                "missing field 'a' (value 6 at byte 64)",
+                "missing field 'b.c' (value 7 at byte 64)",
+                // The line is only checked as far as the search goes.
+                "invalid JSON: expected '}', found '\"' at column 9 (value 9 at byte 71)",
+                "invalid number 'true' (value 11 at byte 107): invalid float literal",
+            ]
+        );
+
+        let (trickled_columns, trickled_skipped, trickled_warnings) = read(false);
+        assert_eq!(trickled_columns, columns);
+        assert_eq!(trickled_skipped.invalid, skipped.invalid);
+        assert_eq!(trickled_warnings.iter().map(ErrorKind::to_string).collect::<Vec<_>>(), warnings);
+
+        let options = ReadOptions { on_error: OnError::Fail, ..options };
+        let mut values = Values::new(&options, Layout::Jsonl, Warnings::Off);
+        let err = values.read_in(data.as_bytes(), 0, &mut Columns::new(Len::default())).unwrap_err();
+        assert!(matches!(err, ErrorKind::Parse { location, .. } if location.offset == 31), "{err}");
+    }
+
+    #[test]
+    fn errors_in_later_chunks_have_input_wide_indices() {
+        let mut data = "1 ".repeat(1_000);
+        data.replace_range(1_500..1_501, "x");
+        let options = ReadOptions::default();
+        for chunks in [1, 4, 9] {
+            let err = scan_chunks(data.as_bytes(), 0, chunks, &options, Layout::Text, false, "test", &mut Len::default())
+                .unwrap_err();
+            match err.kind {
+                ErrorKind::Parse { location, .. } => assert_eq!((location.index, location.offset), (750, 1_500)),
+                kind => panic!("{kind}"),
+            }
+        }
+    }
+
+    /// Quoted fields hold delimiters and line breaks, so a cut in the middle of
+    /// the file lands inside a record; delimited input is read on one thread.
+    #[test]
+    fn delimited_input_is_not_cut_into_chunks() {
+        let mut data = String::from("a,\"b\nc\"\r\n");
+        let mut rng = Rng(12);
+        while data.len() < 3 * MIN_CHUNK {
+            let x = rng.float();
+            data.push_str(&format!("{x},\"{x}\"\n\"x\n,\n\",1\n"));
+        }
+        let file = TestFile::new(&data);
+        let options = |threads| ReadOptions {
+            format: Format::Delimited { delimiter: b',' },
+            on_error: OnError::Skip,
+            threads,
+            ..ReadOptions::default()
+        };
+        let one = scan(&mut file.source(options(1)), Columns::new((Len::default(), Tails::new(4)))).unwrap();
+        let many = scan(&mut file.source(options(8)), Columns::new((Len::default(), Tails::new(4)))).unwrap();
+        assert_eq!(one.0, many.0);
+        assert_eq!(one.1.total(), many.1.total());
+    }
+
+    #[test]
+    fn warnings_are_passed_on_in_input_order() {
+        let mut data = "1 ".repeat(MIN_CHUNK);
+        let bad: Vec<usize> = (0..10).map(|i| 1_000 + i * 200_000).collect();
+        for &at in &bad {
+            data.replace_range(at..at + 1, "x");
+        }
+        let file = TestFile::new(&data);
+        for threads in [1, 4] {
+            let warnings = Arc::new(Mutex::new(Vec::new()));
+            let on_warning = {
+                let warnings = warnings.clone();
+                OnWarning::new(move |warning| warnings.lock().unwrap().push(warning.to_string()))
+            };
+            let options = ReadOptions { on_error: OnError::Warn, on_warning: Some(on_warning), threads, ..ReadOptions::default() };
+            let mut source = file.source(options);
+            let (len, skipped) = scan(&mut source, Len::default()).unwrap();
+            assert_eq!((len, skipped.invalid), (MIN_CHUNK - 10, 10));
+            let warnings = warnings.lock().unwrap();
+            assert_eq!(warnings.len(), 10);
+            for (warning, at) in warnings.iter().zip(&bad) {
+                assert!(warning.contains(&format!("value {} at byte {at})", at / 2)), "{warning}");
+            }
+        }
+    }
+}
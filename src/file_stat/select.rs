@@ -0,0 +1,549 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::{scan, Accumulator, Columns, ErrorKind, Result, Source};
+
+/// How many bits of the key each histogram level resolves.
+const BUCKET_BITS: u32 = 16;
+
+/// A range with at most this many values is read into memory and selected
+/// from directly instead of being split further.
+const COLLECT_LIMIT: u64 = 1 << 22;
+
+/// Maps a float to an integer that sorts the same way as [`f64::total_cmp`].
+fn key(x: f64) -> u64 {
+    let bits = x.to_bits();
+    if bits >> 63 == 1 { !bits } else { bits | 1 << 63 }
+}
+
+fn from_key(key: u64) -> f64 {
+    f64::from_bits(if key >> 63 == 1 { key & !(1 << 63) } else { !key })
+}
+
+/// Counts values by the top bits of a key that sorts like [`f64::total_cmp`].
+///
+/// Gathered during the main scan, it gives both the number of values and the
+/// bucket every rank falls into, so selection starts one level down.
+///
+/// There is one per column and thread, so until it has seen [`SPARSE_LIMIT`]
+/// values it keeps their buckets in a list, which is smaller than the counts.
+#[derive(Clone, Default)]
+pub struct Histogram {
+    /// The bucket of every value, while the counts are not kept.
+    buckets: Vec<u16>,
+    /// Values by bucket, once there are too many for `buckets`; empty before.
+    counts: Vec<u64>,
+    len: u64,
+}
+
+/// Values a [`Histogram`] lists one by one before it counts them instead.
+const SPARSE_LIMIT: usize = 1 << 16;
+
+impl fmt::Debug for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Histogram").field("len", &self.len).finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for Histogram {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.counts() == other.counts()
+    }
+}
+
+impl Histogram {
+    /// Values by bucket, counted from the list if they are not kept.
+    fn counts(&self) -> Cow<'_, [u64]> {
+        if !self.counts.is_empty() {
+            return Cow::Borrowed(&self.counts);
+        }
+        let mut counts = vec![0; 1 << BUCKET_BITS];
+        for &bucket in &self.buckets {
+            counts[bucket as usize] += 1;
+        }
+        Cow::Owned(counts)
+    }
+
+    /// Switches from the list to the counts once the list is too long.
+    fn count_if_full(&mut self) {
+        if self.buckets.len() >= SPARSE_LIMIT {
+            self.counts = self.counts().into_owned();
+            self.buckets = Vec::new();
+        }
+    }
+
+    /// The number of values counted.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether no values were counted.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Accumulator for Histogram {
+    type Output = Histogram;
+
+    fn push(&mut self, x: f64) {
+        let bucket = key(x) >> (64 - BUCKET_BITS);
+        if self.counts.is_empty() {
+            self.buckets.push(bucket as u16);
+            self.count_if_full();
+        } else {
+            self.counts[bucket as usize] += 1;
+        }
+        self.len += 1;
+    }
+
+    fn merge(&mut self, other: Self) {
+        match (self.counts.is_empty(), other.counts.is_empty()) {
+            (true, true) => self.buckets.extend(other.buckets),
+            (true, false) => {
+                let buckets = std::mem::take(&mut self.buckets);
+                self.counts = other.counts;
+                for bucket in buckets {
+                    self.counts[bucket as usize] += 1;
+                }
+            }
+            (false, true) => {
+                for bucket in other.buckets {
+                    self.counts[bucket as usize] += 1;
+                }
+            }
+            (false, false) => {
+                for (count, other) in self.counts.iter_mut().zip(other.counts) {
+                    *count += other;
+                }
+            }
+        }
+        self.count_if_full();
+        self.len += other.len;
+    }
+
+    fn finish(self) -> Histogram {
+        self
+    }
+}
+
+/// Keys that start with the `bits` top bits `prefix`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Range {
+    prefix: u64,
+    bits: u32,
+}
+
+impl Range {
+    fn contains(&self, key: u64) -> bool {
+        key >> (64 - self.bits) == self.prefix
+    }
+}
+
+/// Ranks still looking for their value: the index of the rank in the caller's
+/// slice and the rank counted from the start of the range.
+type Targets = Vec<(usize, u64)>;
+
+/// Moves every target of `range` into the sub-range of `counts` holding it.
+/// A rank beyond the values counted means the input changed since the pass
+/// that found the rank in `range`.
+fn refine(
+    counts: &[u64],
+    range: Range,
+    sub_bits: u32,
+    targets: Targets,
+    out: &mut BTreeMap<Range, (u64, Targets)>,
+) -> std::result::Result<(), ErrorKind> {
+    for (i, rank) in targets {
+        let mut before = 0;
+        let (bucket, count) = counts.iter().enumerate()
+            .find(|&(_, &count)| {
+                before += count;
+                rank < before
+            })
+            .ok_or(ErrorKind::Changed)?;
+        let sub_range = Range { prefix: range.prefix << sub_bits | bucket as u64, bits: range.bits + sub_bits };
+        out.entry(sub_range).or_insert((*count, Vec::new())).1.push((i, rank - (before - count)));
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+enum Work {
+    Collect(Vec<f64>),
+    Count { sub_bits: u32, counts: Vec<u64> },
+}
+
+/// One selection pass: gathers what each pending range needs to know.
+#[derive(Clone, Default)]
+struct Gather(Vec<(Range, Work)>);
+
+impl Accumulator for Gather {
+    type Output = Vec<(Range, Work)>;
+
+    fn push(&mut self, x: f64) {
+        let key = key(x);
+        for (range, work) in self.0.iter_mut().filter(|(range, _)| range.contains(key)) {
+            match work {
+                Work::Collect(values) => values.push(x),
+                Work::Count { sub_bits, counts } => {
+                    let shift = 64 - range.bits - *sub_bits;
+                    counts[((key >> shift) & ((1 << *sub_bits) - 1)) as usize] += 1;
+                }
+            }
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        for ((_, work), (_, other)) in self.0.iter_mut().zip(other.0) {
+            match (work, other) {
+                (Work::Collect(values), Work::Collect(other)) => values.extend(other),
+                (Work::Count { counts, .. }, Work::Count { counts: other, .. }) => {
+                    for (count, other) in counts.iter_mut().zip(other) {
+                        *count += other;
+                    }
+                }
+                _ => unreachable!("merging different passes"),
+            }
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        self.0
+    }
+}
+
+/// Finds the exact values at the given 0-based `ranks` of the sorted values
+/// of every column, where `ranks[i]` and `histograms[i]` belong to column `i`.
+///
+/// Each pass either splits the ranges holding the ranks by the next
+/// [`BUCKET_BITS`] bits of the key, or, once a range holds at most
+/// `collect_limit` values, reads them into memory. Usually one pass after the main scan is enough,
+/// and all columns share the same passes.
+///
+/// Every pass must see as many values in a range as the pass before counted
+/// there; an input that changes in between fails with [`ErrorKind::Changed`].
+fn select(source: &mut Source, histograms: &[Histogram], ranks: &[Vec<u64>], collect_limit: u64) -> Result<Vec<Vec<f64>>> {
+    let mut values: Vec<_> = ranks.iter().map(|ranks| vec![f64::NAN; ranks.len()]).collect();
+    let mut pending: Vec<_> = histograms.iter().zip(ranks)
+        .map(|(histogram, ranks)| {
+            let mut pending = BTreeMap::new();
+            let whole = Range { prefix: 0, bits: 0 };
+            refine(&histogram.counts(), whole, BUCKET_BITS, ranks.iter().copied().enumerate().collect(), &mut pending)
+                .map(|()| pending)
+        })
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|kind: ErrorKind| kind.at(source.name()))?;
+
+    loop {
+        for (pending, values) in pending.iter_mut().zip(&mut values) {
+            // Keys that agree on all bits are the same value.
+            pending.retain(|range, (_, targets)| {
+                if range.bits < 64 {
+                    return true;
+                }
+                for &(i, _) in targets.iter() {
+                    values[i] = from_key(range.prefix);
+                }
+                false
+            });
+        }
+        if pending.iter().all(BTreeMap::is_empty) {
+            break;
+        }
+
+        let work = pending.iter()
+            .map(|pending| {
+                Gather(pending.iter()
+                    .map(|(&range, &(count, _))| {
+                        let work = if count <= collect_limit {
+                            Work::Collect(Vec::new())
+                        } else {
+                            let sub_bits = BUCKET_BITS.min(64 - range.bits);
+                            Work::Count { sub_bits, counts: vec![0; 1 << sub_bits] }
+                        };
+                        (range, work)
+                    })
+                    .collect())
+            })
+            .collect();
+
+        let (work, _) = scan(source, Columns::with_columns(work, Gather::default()))?;
+
+        for ((work, pending), values) in work.into_iter().zip(&mut pending).zip(&mut values) {
+            let mut next = BTreeMap::new();
+            for ((range, work), (_, (count, targets))) in work.into_iter().zip(std::mem::take(pending)) {
+                let found = match &work {
+                    Work::Collect(collected) => collected.len() as u64,
+                    Work::Count { counts, .. } => counts.iter().sum(),
+                };
+                if found != count {
+                    return Err(ErrorKind::Changed.at(source.name()));
+                }
+                match work {
+                    Work::Collect(mut collected) => {
+                        for (i, rank) in targets {
+                            values[i] = *collected.select_nth_unstable_by(rank as usize, f64::total_cmp).1;
+                        }
+                    }
+                    Work::Count { sub_bits, counts } => {
+                        refine(&counts, range, sub_bits, targets, &mut next).map_err(|kind| kind.at(source.name()))?
+                    }
+                }
+            }
+            *pending = next;
+        }
+    }
+
+    Ok(values)
+}
+
+/// How a quantile that falls between two values is computed, as in numpy.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Interpolation {
+    /// Along the line between the two values.
+    #[default]
+    Linear,
+    /// The smaller value.
+    Lower,
+    /// The larger value.
+    Higher,
+    /// Halfway between the two values.
+    Midpoint,
+    /// The closer value; halfway goes to the even rank.
+    Nearest,
+}
+
+/// The values around a quantile: the values at ranks `index` and `index + 1`
+/// of the sorted input, and how far between them it falls. A quantile that
+/// falls right on rank `index` has that value as both.
+#[derive(Clone, Copy, Debug)]
+pub struct Quantile {
+    /// Rank of `lower`, counting from 0.
+    pub index: u64,
+    /// How far from `lower` to `upper` the quantile falls, from 0 to 1.
+    pub fraction: f64,
+    /// The value at rank `index`.
+    pub lower: f64,
+    /// The value at rank `index + 1`, or `lower` again when `fraction` is 0.
+    pub upper: f64,
+}
+
+impl Quantile {
+    /// The quantile, computed by `method`.
+    pub fn value(&self, method: Interpolation) -> f64 {
+        let (a, b, t) = (self.lower, self.upper, self.fraction);
+        match method {
+            // Interpolating from the nearer end keeps the result monotonic in `t`.
+            Interpolation::Linear if t < 0.5 => a + (b - a) * t,
+            Interpolation::Linear => b - (b - a) * (1.0 - t),
+            Interpolation::Lower => a,
+            // A quantile that lands exactly on a value has nothing to round or average.
+            Interpolation::Higher | Interpolation::Midpoint if t == 0.0 => a,
+            Interpolation::Higher => b,
+            Interpolation::Midpoint => a + (b - a) / 2.0,
+            Interpolation::Nearest if t < 0.5 || (t == 0.5 && self.index.is_multiple_of(2)) => a,
+            Interpolation::Nearest => b,
+        }
+    }
+}
+
+/// Finds the values around every quantile `qs` (each within `0..=1`) of
+/// every column, in the same passes over the input, which must follow a scan
+/// with a [`Histogram`] for each column.
+///
+/// A column without values has no quantiles. A one-shot input must have been
+/// marked with [`Source::keep_for_rereads`] before its first pass.
+pub fn quantiles(source: &mut Source, histograms: &[Histogram], qs: &[f64]) -> Result<Vec<Vec<Quantile>>> {
+    let positions: Vec<Vec<_>> = histograms.iter()
+        .map(|histogram| {
+            let len = histogram.len();
+            let qs = if len == 0 { &[][..] } else { qs };
+            qs.iter()
+                .map(|&q| {
+                    let h = (len - 1) as f64 * q;
+                    let index = (h.floor() as u64).min(len - 1);
+                    (index, h - index as f64)
+                })
+                .collect()
+        })
+        .collect();
+    let ranks: Vec<Vec<_>> = positions.iter()
+        .map(|positions| {
+            positions.iter()
+                .flat_map(|&(index, fraction)| [index, if fraction == 0.0 { index } else { index + 1 }])
+                .collect()
+        })
+        .collect();
+    let values = select(source, histograms, &ranks, COLLECT_LIMIT)?;
+
+    Ok(positions.into_iter().zip(values)
+        .map(|(positions, values)| {
+            positions.into_iter().zip(values.chunks(2))
+                .map(|((index, fraction), pair)| Quantile { index, fraction, lower: pair[0], upper: pair[1] })
+                .collect()
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_key, key, quantiles, select, Histogram, Interpolation, Quantile, COLLECT_LIMIT, SPARSE_LIMIT};
+    use crate::file_stat::testing::{Rng, TestFile};
+    use crate::file_stat::{scan, Accumulator, ErrorKind, ReadOptions};
+
+    /// The values at `ranks` of the sorted `values`, found by selection.
+    fn select_ranks(values: &[f64], ranks: &[u64], collect_limit: u64) -> Vec<f64> {
+        let file = TestFile::with_values(values);
+        let mut source = file.source(ReadOptions::default());
+        let (histogram, _) = scan(&mut source, Histogram::default()).unwrap();
+        select(&mut source, &[histogram], &[ranks.to_vec()], collect_limit).unwrap().remove(0)
+    }
+
+    fn sorted(values: &[f64]) -> Vec<f64> {
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        sorted
+    }
+
+    #[test]
+    fn keys_round_trip_and_sort_like_total_cmp() {
+        let mut values = vec![
+            0.0, -0.0, 1.0, -1.0, 1.5, -1.5, f64::MIN_POSITIVE, -f64::MIN_POSITIVE, 5e-324, -5e-324,
+            f64::MAX, f64::MIN, f64::INFINITY, f64::NEG_INFINITY, f64::NAN, -f64::NAN,
+            f64::from_bits(0x7ff0_0000_0000_0001), f64::from_bits(0xfff8_0000_dead_beef),
+        ];
+        for x in &values {
+            assert_eq!(from_key(key(*x)).to_bits(), x.to_bits(), "{x:e}");
+        }
+        assert!(key(-0.0) < key(0.0));
+        values.sort_by_key(|&x| key(x));
+        let by_key: Vec<u64> = values.iter().map(|x| x.to_bits()).collect();
+        values.sort_by(f64::total_cmp);
+        assert_eq!(by_key, values.iter().map(|x| x.to_bits()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn collects_small_ranges() {
+        let values = Rng(1).values(10_000);
+        let ranks = [0, 1, 4_999, 5_000, 9_998, 9_999];
+        let expected: Vec<f64> = ranks.iter().map(|&rank| sorted(&values)[rank as usize]).collect();
+        assert_eq!(select_ranks(&values, &ranks, COLLECT_LIMIT), expected);
+    }
+
+    #[test]
+    fn refines_over_several_levels() {
+        // All in the same top-level bucket, and partly equal, so that a tiny
+        // limit makes ranges split down to single keys.
+        let mut rng = Rng(2);
+        let values: Vec<f64> = (0..5_000)
+            .map(|_| if rng.below(4) == 0 { 1.0 + 1e-9 } else { 1.0 + rng.float() * 1e-3 })
+            .collect();
+        let ranks: Vec<u64> = (0..5_000).step_by(97).chain([4_999]).collect();
+        let expected: Vec<f64> = ranks.iter().map(|&rank| sorted(&values)[rank as usize]).collect();
+        for limit in [0, 4, 100] {
+            assert_eq!(select_ranks(&values, &ranks, limit), expected, "limit {limit}");
+        }
+    }
+
+    #[test]
+    fn fails_when_the_input_changes_between_passes() {
+        for (contents, limit) in [("1\n2\n", COLLECT_LIMIT), ("1\n2\n", 0), ("1\n2\n3\n4\n4\n", 0), ("", 1)] {
+            let file = TestFile::with_values(&[1.0, 2.0, 3.0, 4.0]);
+            let mut source = file.source(ReadOptions::default());
+            let (histogram, _) = scan(&mut source, Histogram::default()).unwrap();
+            file.rewrite(contents);
+            let err = select(&mut source, &[histogram], &[vec![3]], limit).unwrap_err();
+            assert!(matches!(err.kind, ErrorKind::Changed), "{contents:?}: {err}");
+        }
+    }
+
+    #[test]
+    fn histograms_count_the_same_listed_or_counted() {
+        let values = Rng(3).values(SPARSE_LIMIT + 1_000);
+        let mut dense = Histogram::default();
+        values.iter().for_each(|&x| dense.push(x));
+        assert!(!dense.counts.is_empty());
+
+        // Halves that each stay a list, a list into counts and counts into a list.
+        let (left, right) = values.split_at(values.len() / 2);
+        let (mut a, mut b) = (Histogram::default(), Histogram::default());
+        left.iter().for_each(|&x| a.push(x));
+        right.iter().for_each(|&x| b.push(x));
+        assert!(a.counts.is_empty() && b.counts.is_empty());
+        let mut merged = a.clone();
+        merged.merge(b.clone());
+        assert!(!merged.counts.is_empty());
+        assert!(merged == dense);
+
+        let mut few = Histogram::default();
+        few.push(values[0]);
+        let mut rest = Histogram::default();
+        values[1..].iter().for_each(|&x| rest.push(x));
+        let mut listed = few.clone();
+        listed.merge(rest.clone());
+        rest.merge(few);
+        assert!(listed == dense && rest == dense);
+    }
+
+    fn medians(values: &[f64]) -> Quantile {
+        let file = TestFile::with_values(values);
+        let mut source = file.source(ReadOptions::default());
+        let (histogram, _) = scan(&mut source, Histogram::default()).unwrap();
+        quantiles(&mut source, &[histogram], &[0.5]).unwrap()[0][0]
+    }
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        let odd = medians(&[20.0, 1.0, 10.0, 3.0, 2.0]);
+        assert_eq!((odd.index, odd.fraction, odd.lower, odd.upper), (2, 0.0, 3.0, 3.0));
+        assert_eq!(odd.value(Interpolation::Midpoint), 3.0);
+
+        let even = medians(&[10.0, 1.0, 3.0, 2.0]);
+        assert_eq!((even.index, even.fraction, even.lower, even.upper), (1, 0.5, 2.0, 3.0));
+        assert_eq!(even.value(Interpolation::Midpoint), 2.5);
+
+        let one = medians(&[7.0]);
+        assert_eq!((one.lower, one.upper), (7.0, 7.0));
+    }
+
+    #[test]
+    fn interpolates_like_numpy() {
+        use Interpolation::{Higher, Linear, Lower, Midpoint, Nearest};
+
+        // numpy.quantile(a, q, method=...) of a = [7, 1, 11, 2, 4], which sorts
+        // to [1, 2, 4, 7, 11]: q of 0.125, 0.375 and 0.625 fall exactly halfway
+        // between ranks, and "nearest" takes the even one of the two.
+        let table = [
+            (0.0, [1.0, 1.0, 1.0, 1.0, 1.0]),
+            (0.125, [1.5, 1.0, 2.0, 1.0, 1.5]),
+            (0.25, [2.0, 2.0, 2.0, 2.0, 2.0]),
+            (0.3, [2.4, 2.0, 4.0, 2.0, 3.0]),
+            (0.375, [3.0, 2.0, 4.0, 4.0, 3.0]),
+            (0.5, [4.0, 4.0, 4.0, 4.0, 4.0]),
+            (0.625, [5.5, 4.0, 7.0, 4.0, 5.5]),
+            (0.9, [9.4, 7.0, 11.0, 11.0, 9.0]),
+            (1.0, [11.0, 11.0, 11.0, 11.0, 11.0]),
+        ];
+        let file = TestFile::with_values(&[7.0, 1.0, 11.0, 2.0, 4.0]);
+        let mut source = file.source(ReadOptions::default());
+        let (histogram, _) = scan(&mut source, Histogram::default()).unwrap();
+        let qs: Vec<f64> = table.iter().map(|&(q, _)| q).collect();
+        let found = quantiles(&mut source, &[histogram], &qs).unwrap().remove(0);
+        for ((q, expected), quantile) in table.iter().zip(found) {
+            for (method, expected) in [Linear, Lower, Higher, Nearest, Midpoint].into_iter().zip(expected) {
+                let value = quantile.value(method);
+                assert!((value - expected).abs() < 1e-12, "q {q}, {method:?}: {value}, not {expected}");
+            }
+        }
+    }
+
+    #[test]
+    fn columns_without_values_have_no_quantiles() {
+        let file = TestFile::new("");
+        let mut source = file.source(ReadOptions::default());
+        let (histogram, _) = scan(&mut source, Histogram::default()).unwrap();
+        assert!(quantiles(&mut source, &[histogram], &[0.5]).unwrap()[0].is_empty());
+    }
+}
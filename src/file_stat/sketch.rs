@@ -0,0 +1,187 @@
+use super::Accumulator;
+
+/// A KLL quantile sketch: approximate quantiles from one pass in bounded memory.
+///
+/// Values go into a stack of compactors. When a level fills up it is sorted and
+/// every other value, starting at a random one, moves up a level where it
+/// stands for twice as many values. Level capacities shrink by 2/3 going down,
+/// so about `3k` values are kept however long the input is.
+#[derive(Clone, Debug)]
+pub struct Kll {
+    k: usize,
+    levels: Vec<Vec<f64>>,
+    /// Values held across all levels, and how many the levels can hold.
+    size: usize,
+    max_size: usize,
+    rng: u64,
+}
+
+impl Kll {
+    const MIN_K: usize = 8;
+    const MAX_K: usize = 1 << 16;
+
+    /// A sketch that keeps about `k` values on its top level; larger is more accurate.
+    pub fn new(k: usize) -> Self {
+        let k = k.clamp(Self::MIN_K, Self::MAX_K);
+        Kll { k, levels: vec![Vec::new()], size: 0, max_size: k, rng: 0x9e37_79b9_7f4a_7c15 }
+    }
+
+    /// The smallest sketch whose [`rank_error`](Self::rank_error) is at most `epsilon`.
+    pub fn with_accuracy(epsilon: f64) -> Self {
+        let k = (RANK_ERROR_SCALE / epsilon).powf(1.0 / RANK_ERROR_EXPONENT).ceil();
+        Kll::new(if k.is_finite() { k as usize } else { Self::MAX_K })
+    }
+
+    /// The normalized rank error at 99% confidence: a quantile `q` is reported
+    /// as a value whose true rank lies within `q ± rank_error`.
+    pub fn rank_error(&self) -> f64 {
+        RANK_ERROR_SCALE / (self.k as f64).powf(RANK_ERROR_EXPONENT)
+    }
+
+    fn capacity(&self, level: usize) -> usize {
+        let depth = (self.levels.len() - 1 - level) as i32;
+        ((self.k as f64) * (2.0f64 / 3.0).powi(depth)).ceil().max(2.0) as usize
+    }
+
+    /// A random bit from xorshift64; the seed is fixed so results are reproducible.
+    fn coin(&mut self) -> usize {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 32) as usize & 1
+    }
+
+    /// Halves the lowest level that is over its capacity.
+    fn compact(&mut self) {
+        let Some(level) = (0..self.levels.len()).find(|&level| self.levels[level].len() >= self.capacity(level)) else {
+            return;
+        };
+        if level + 1 == self.levels.len() {
+            self.levels.push(Vec::new());
+            self.max_size = (0..self.levels.len()).map(|level| self.capacity(level)).sum();
+        }
+
+        let mut values = std::mem::take(&mut self.levels[level]);
+        values.sort_unstable_by(f64::total_cmp);
+        if values.len() % 2 == 1 {
+            self.levels[level].extend(values.pop());
+        }
+        self.size -= values.len() / 2;
+        let offset = self.coin();
+        self.levels[level + 1].extend(values.into_iter().skip(offset).step_by(2));
+    }
+
+    /// The value at quantile `q` (within `0..=1`), or `None` for an empty sketch.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let mut weighted: Vec<(f64, u64)> = self.levels.iter().enumerate()
+            .flat_map(|(level, values)| values.iter().map(move |&x| (x, 1u64 << level)))
+            .collect();
+        weighted.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+        let total: u64 = weighted.iter().map(|&(_, weight)| weight).sum();
+        let rank = q * total as f64;
+        let mut seen = 0;
+        for &(x, weight) in &weighted {
+            seen += weight;
+            if seen as f64 > rank {
+                return Some(x);
+            }
+        }
+        weighted.last().map(|&(x, _)| x)
+    }
+}
+
+/// Fit of the 99%-confidence single-quantile rank error of a KLL sketch
+/// against `k`, as published with the Apache DataSketches implementation.
+const RANK_ERROR_SCALE: f64 = 2.296;
+const RANK_ERROR_EXPONENT: f64 = 0.9723;
+
+impl Accumulator for Kll {
+    type Output = Kll;
+
+    fn push(&mut self, x: f64) {
+        self.levels[0].push(x);
+        self.size += 1;
+        while self.size >= self.max_size {
+            self.compact();
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        if other.levels.len() > self.levels.len() {
+            self.levels.resize_with(other.levels.len(), Vec::new);
+            self.max_size = (0..self.levels.len()).map(|level| self.capacity(level)).sum();
+        }
+        for (level, values) in other.levels.into_iter().enumerate() {
+            self.levels[level].extend(values);
+        }
+        self.size += other.size;
+        while self.size >= self.max_size {
+            self.compact();
+        }
+    }
+
+    fn finish(self) -> Kll {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Kll;
+    use crate::file_stat::testing::Rng;
+    use crate::file_stat::Accumulator;
+
+    /// Checks that every percentile the sketch reports has a true rank within
+    /// its rank error of the percentile.
+    fn check_ranks(sketch: &Kll, values: &[f64]) {
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let len = sorted.len() as f64;
+        let epsilon = sketch.rank_error();
+        for p in 1..100 {
+            let q = p as f64 / 100.0;
+            let x = sketch.quantile(q).unwrap();
+            // Ranks of the values equal to `x` span from `below` to `upto`.
+            let below = sorted.partition_point(|&y| y < x) as f64 / len;
+            let upto = sorted.partition_point(|&y| y <= x) as f64 / len;
+            assert!(below - epsilon <= q && q <= upto + epsilon, "q {q}: {x} has ranks {below}..{upto}, error {epsilon}");
+        }
+    }
+
+    #[test]
+    fn rank_error_holds_for_one_sketch() {
+        let values = Rng(3).values(200_000);
+        for epsilon in [0.05, 0.01, 0.002] {
+            let mut sketch = Kll::with_accuracy(epsilon);
+            assert!(sketch.rank_error() <= epsilon);
+            values.iter().for_each(|&x| sketch.push(x));
+            check_ranks(&sketch, &values);
+        }
+    }
+
+    #[test]
+    fn rank_error_holds_after_merging() {
+        let values = Rng(4).values(200_000);
+        let sketches = values.chunks(17_000).map(|chunk| {
+            let mut sketch = Kll::with_accuracy(0.01);
+            chunk.iter().for_each(|&x| sketch.push(x));
+            sketch
+        });
+        let merged = sketches.reduce(|mut merged, sketch| {
+            merged.merge(sketch);
+            merged
+        });
+        check_ranks(&merged.unwrap(), &values);
+    }
+
+    #[test]
+    fn small_inputs_are_exact() {
+        let mut sketch = Kll::new(200);
+        assert_eq!(sketch.quantile(0.5), None);
+        (1..=100).for_each(|x| sketch.push(x as f64));
+        assert_eq!(sketch.quantile(0.0), Some(1.0));
+        assert_eq!(sketch.quantile(0.5), Some(51.0));
+        assert_eq!(sketch.quantile(1.0), Some(100.0));
+    }
+}
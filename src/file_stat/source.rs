@@ -0,0 +1,382 @@
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, prelude::*, BufWriter};
+use std::ops::Deref;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use memmap2::Mmap;
+
+use super::{compression, Decompress, ReadOptions};
+
+/// Where the values come from: a file, or stdin when the name is `-`.
+///
+/// Regular files can be opened as many times as the statistics need. Stdin,
+/// pipes and FIFOs can only be read once, so when more than one pass is needed
+/// the first pass copies everything it reads into a temporary spool file and
+/// the later passes read that copy instead. Compressed inputs, even regular
+/// files, are decompressed as they are read, so they are one-shot as well.
+///
+/// Regular files, including the spool, are mapped into memory and tokenized in
+/// place; everything else goes through a buffer.
+#[derive(Debug)]
+pub struct Source {
+    path: PathBuf,
+    /// `path` as text, for messages and reports.
+    name: String,
+    options: ReadOptions,
+    spool: Option<Spool>,
+    reread: bool,
+    passes: usize,
+    column_names: Vec<String>,
+    scattered_rows: bool,
+}
+
+/// One pass worth of input: the whole file mapped into memory, or a stream.
+pub enum Input {
+    /// A regular file, mapped as a whole.
+    Mapped(Mapped),
+    /// Anything that is read from start to end.
+    Stream(Box<dyn Read>),
+}
+
+/// The bytes of a regular file mapped into memory, until dropped.
+#[derive(Debug)]
+pub struct Mapped(Mmap);
+
+impl fmt::Debug for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Input::Mapped(map) => f.debug_tuple("Mapped").field(map).finish(),
+            Input::Stream(_) => f.debug_tuple("Stream").finish_non_exhaustive(),
+        }
+    }
+}
+
+impl Deref for Mapped {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[derive(Debug)]
+struct Spool {
+    file: TempFile,
+    complete: Arc<AtomicBool>,
+}
+
+impl Source {
+    /// The input at `path`, or standard input for `-`. Nothing is opened
+    /// before the first pass.
+    pub fn new(path: impl AsRef<Path>, options: ReadOptions) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let name = path.to_string_lossy().into_owned();
+        Source { path, name, options, spool: None, reread: false, passes: 0, column_names: Vec::new(), scattered_rows: false }
+    }
+
+    /// The path the input was named by.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The path as text, with anything that is not UTF-8 replaced.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// How values are read out of the input.
+    pub fn options(&self) -> &ReadOptions {
+        &self.options
+    }
+
+    /// Names of the columns found by the last pass, for inputs whose columns have names.
+    pub fn column_names(&self) -> &[String] {
+        &self.column_names
+    }
+
+    pub(super) fn set_column_names(&mut self, names: Vec<String>) {
+        self.column_names = names;
+    }
+
+    /// Whether the last pass found columns whose rows are spread over the
+    /// data, as in a Fortran-order array, so that no row was ever ended.
+    pub(super) fn scattered_rows(&self) -> bool {
+        self.scattered_rows
+    }
+
+    pub(super) fn set_scattered_rows(&mut self, scattered: bool) {
+        self.scattered_rows = scattered;
+    }
+
+    /// Announces that the input will be read more than once, so that a
+    /// one-shot input gets spooled during its first pass.
+    pub fn keep_for_rereads(&mut self) {
+        self.reread = true;
+    }
+
+    fn is_stdin(&self) -> bool {
+        self.path == Path::new("-")
+    }
+
+    /// Whether the input can be read again from the start without spooling.
+    pub fn is_rewindable(&self) -> bool {
+        !self.is_stdin() && fs::metadata(&self.path).is_ok_and(|meta| meta.is_file()) && !self.is_compressed_file()
+    }
+
+    /// Whether the named file is to be decompressed.
+    fn is_compressed_file(&self) -> bool {
+        match self.options.decompress {
+            Decompress::Never => false,
+            Decompress::Always(_) => true,
+            Decompress::Detect => File::open(&self.path)
+                .and_then(|file| compression::sniff(Box::new(file)))
+                .is_ok_and(|(codec, _)| codec.is_some()),
+        }
+    }
+
+    /// Wraps one-shot `input` in a decoder, if it is compressed.
+    fn decompress(&self, input: Box<dyn Read>) -> io::Result<Box<dyn Read>> {
+        let (codec, input) = match self.options.decompress {
+            Decompress::Never => return Ok(input),
+            Decompress::Always(codec) => (Some(codec), input),
+            Decompress::Detect => compression::sniff(input)?,
+        };
+        match codec {
+            Some(codec) => codec.decoder(input),
+            None => Ok(input),
+        }
+    }
+
+    /// How many passes have been started so far, counting the current one.
+    pub fn passes(&self) -> usize {
+        self.passes
+    }
+
+    /// Reads a regular file, mapping it into memory unless that is turned off.
+    fn open_file(&self, file: File) -> io::Result<Input> {
+        if !self.options.mmap {
+            return Ok(Input::Stream(Box::new(file)));
+        }
+
+        // SAFETY: the map is only read, and only while this pass lasts. If another
+        // process truncates the file meanwhile, reads fault just as they would on
+        // any other mapped file; that is the usual trade-off for not copying.
+        let map = unsafe { Mmap::map(&file)? };
+        #[cfg(unix)]
+        let _ = map.advise(memmap2::Advice::Sequential);
+        Ok(Input::Mapped(Mapped(map)))
+    }
+
+    /// Opens the input for another pass over the values.
+    pub fn open(&mut self) -> io::Result<Input> {
+        self.passes += 1;
+        if self.is_rewindable() {
+            return self.open_file(File::open(&self.path)?);
+        }
+
+        if let Some(spool) = &self.spool {
+            if !spool.complete.load(Ordering::Acquire) {
+                return Err(io::Error::other("the previous pass did not read the whole input"));
+            }
+            // The handle the spool was created with, rather than whatever
+            // might have been put at its path since.
+            let mut file = spool.file.file.try_clone()?;
+            file.rewind()?;
+            return self.open_file(file);
+        }
+        if self.passes > 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "input is not seekable and cannot be read twice; statistics like the median need a regular file",
+            ));
+        }
+
+        let input: Box<dyn Read> = if self.is_stdin() {
+            Box::new(io::stdin().lock())
+        } else {
+            Box::new(File::open(&self.path)?)
+        };
+        let input = self.decompress(input)?;
+        if !self.reread {
+            return Ok(Input::Stream(input));
+        }
+
+        let file = TempFile::new().map_err(|err| {
+            io::Error::new(err.kind(), format!("cannot buffer non-seekable input for another pass: {err}"))
+        })?;
+        let writer = BufWriter::new(file.file.try_clone()?);
+        let complete = Arc::new(AtomicBool::new(false));
+        self.spool = Some(Spool { file, complete: complete.clone() });
+
+        Ok(Input::Stream(Box::new(Tee { input, writer, complete })))
+    }
+}
+
+/// Copies everything read from `input` into `writer`.
+struct Tee<R> {
+    input: R,
+    writer: BufWriter<File>,
+    complete: Arc<AtomicBool>,
+}
+
+impl<R: Read> Read for Tee<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.input.read(buf)?;
+        if n == 0 {
+            self.writer.flush()?;
+            self.complete.store(true, Ordering::Release);
+        } else {
+            self.writer.write_all(&buf[..n])?;
+        }
+        Ok(n)
+    }
+}
+
+/// A file in the system temporary directory that is removed on drop.
+///
+/// Only the owner may read it, as it holds a copy of the input, and it is
+/// only ever used through the handle it was created with.
+#[derive(Debug)]
+struct TempFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl TempFile {
+    fn new() -> io::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        loop {
+            let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+            let path = std::env::temp_dir().join(format!("bigfilestat-{}-{}.spool", std::process::id(), n));
+            let mut options = OpenOptions::new();
+            options.read(true).write(true).create_new(true);
+            #[cfg(unix)]
+            options.mode(0o600);
+            match options.open(&path) {
+                Ok(file) => return Ok(TempFile { path, file }),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+    use std::io::{self, Read, Write};
+    use std::path::PathBuf;
+    use std::process::Command;
+    use std::thread::{self, JoinHandle};
+
+    use super::{Input, Source};
+    use crate::file_stat::{quantiles, scan, summarize, Histogram, Interpolation, ReadOptions, Stats};
+
+    /// A named pipe that a thread writes `contents` into once it is opened,
+    /// removed on drop.
+    struct Fifo {
+        path: PathBuf,
+        writer: Option<JoinHandle<()>>,
+    }
+
+    impl Fifo {
+        fn new(name: &str, contents: &'static [u8]) -> Self {
+            let path = std::env::temp_dir().join(format!("bigfilestat-test-{}-{name}.fifo", std::process::id()));
+            let _ = fs::remove_file(&path);
+            assert!(Command::new("mkfifo").arg(&path).status().unwrap().success());
+            let writer_path = path.clone();
+            // Opening blocks until the source opens the other end. A reader
+            // that stops early makes the write fail, which is fine here.
+            let writer = thread::spawn(move || {
+                let _ = fs::OpenOptions::new().write(true).open(writer_path).and_then(|mut fifo| fifo.write_all(contents));
+            });
+            Fifo { path, writer: Some(writer) }
+        }
+    }
+
+    impl Drop for Fifo {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+            if let Some(writer) = self.writer.take() {
+                let _ = writer.join();
+            }
+        }
+    }
+
+    fn read_all(input: Input) -> io::Result<Vec<u8>> {
+        match input {
+            Input::Mapped(map) => Ok(map.to_vec()),
+            Input::Stream(mut reader) => {
+                let mut data = Vec::new();
+                reader.read_to_end(&mut data)?;
+                Ok(data)
+            }
+        }
+    }
+
+    #[test]
+    fn spools_pipes_for_another_pass() {
+        let fifo = Fifo::new("spool", b"3\n1\n2\n");
+        let mut source = Source::new(&fifo.path, ReadOptions::default());
+        assert!(!source.is_rewindable());
+        source.keep_for_rereads();
+        assert!(matches!(source.open().unwrap(), Input::Stream(_)));
+        // Not read to the end yet, so there is nothing to read again.
+        let err = source.open().unwrap_err();
+        assert!(err.to_string().contains("did not read the whole input"), "{err}");
+
+        let fifo = Fifo::new("spool-read", b"3\n1\n2\n");
+        let mut source = Source::new(&fifo.path, ReadOptions::default());
+        source.keep_for_rereads();
+        assert_eq!(read_all(source.open().unwrap()).unwrap(), b"3\n1\n2\n");
+        let spool = source.spool.as_ref().unwrap().file.path.clone();
+        assert!(spool.exists());
+        for _ in 0..2 {
+            let input = source.open().unwrap();
+            assert!(matches!(input, Input::Mapped(_)));
+            assert_eq!(read_all(input).unwrap(), b"3\n1\n2\n");
+        }
+        drop(source);
+        assert!(!spool.exists(), "{} is left behind", spool.display());
+    }
+
+    #[test]
+    fn pipes_are_read_once_unless_kept() {
+        let fifo = Fifo::new("once", b"1\n2\n");
+        let mut source = Source::new(&fifo.path, ReadOptions::default());
+        assert_eq!(read_all(source.open().unwrap()).unwrap(), b"1\n2\n");
+        let err = source.open().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert!(err.to_string().contains("not seekable"), "{err}");
+        assert!(source.spool.is_none());
+    }
+
+    #[test]
+    fn takes_the_median_of_a_pipe() {
+        let fifo = Fifo::new("median", b"5\n1\n4\n2\n3\n");
+        let mut source = Source::new(&fifo.path, ReadOptions::default());
+        let stats = Stats { median: true, ..Stats::default() };
+        let report = summarize(&mut source, &stats).unwrap();
+        assert!(source.passes() > 1);
+        assert_eq!(report.columns[0].median.unwrap().value(Interpolation::Linear), 3.0);
+
+        // Without asking for rereads first, the second pass has nothing to read.
+        let fifo = Fifo::new("median-unkept", b"5\n1\n4\n2\n3\n");
+        let mut source = Source::new(&fifo.path, ReadOptions::default());
+        let (histogram, _) = scan(&mut source, Histogram::default()).unwrap();
+        let err = quantiles(&mut source, &[histogram], &[0.5]).unwrap_err();
+        assert!(err.to_string().contains("not seekable"), "{err}");
+    }
+}
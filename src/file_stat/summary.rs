@@ -0,0 +1,414 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use super::{
+    quantiles, scan, Accumulator, Columns, Comoments, Error, ErrorKind, Histogram, Interpolation, Kll, Len, MinMax, Moments,
+    Quantile, Result, Skipped, Source, Tails,
+};
+
+/// Which statistics [`summarize`] computes.
+#[derive(Clone, Default, Debug)]
+pub struct Stats {
+    /// The number of values.
+    pub len: bool,
+    /// The smallest and the largest value.
+    pub min_max: bool,
+    /// The arithmetic mean.
+    pub mean: bool,
+    /// Population and sample variance, and the standard deviation.
+    pub variance: bool,
+    /// Skewness.
+    pub skewness: bool,
+    /// Excess kurtosis.
+    pub kurtosis: bool,
+    /// The exact median, halfway between the two middle values.
+    pub median: bool,
+    /// Exact percentiles, from 0 to 100.
+    pub percentiles: Vec<f64>,
+    /// The exact interquartile range, P75 - P25.
+    pub iqr: bool,
+    /// How percentiles and the IQR fall between two values.
+    pub interpolation: Interpolation,
+    /// Percentiles estimated in one pass with a [`Kll`] sketch.
+    pub approx_percentiles: Vec<f64>,
+    /// The rank error the sketch may make; 1% when `None`.
+    pub accuracy: Option<f64>,
+    /// How many of the first and the last values to keep.
+    pub tails: Option<usize>,
+    /// The covariance matrix of the columns.
+    pub covariance: bool,
+    /// The correlation matrix of the columns.
+    pub correlation: bool,
+}
+
+impl Stats {
+    /// How many values of each tail are printed when tails are not asked for explicitly.
+    const DEFAULT_TAILS: usize = 10;
+    const DEFAULT_ACCURACY: f64 = 0.01;
+
+    /// Every statistic that needs no parameters, and tails of 10 values.
+    pub fn all() -> Self {
+        Stats {
+            len: true,
+            min_max: true,
+            mean: true,
+            variance: true,
+            skewness: true,
+            kurtosis: true,
+            median: true,
+            tails: Some(Self::DEFAULT_TAILS),
+            ..Stats::default()
+        }
+    }
+
+    /// Whether no statistic at all is asked for.
+    pub fn is_empty(&self) -> bool {
+        !(self.len || self.min_max || self.moments() || self.median)
+            && self.tails.is_none()
+            && self.percentiles.is_empty()
+            && !self.iqr
+            && self.approx_percentiles.is_empty()
+            && !(self.covariance || self.correlation)
+    }
+
+    /// The rank error the sketch may make.
+    pub fn accuracy(&self) -> f64 {
+        self.accuracy.unwrap_or(Self::DEFAULT_ACCURACY)
+    }
+
+    /// Whether any statistic from [`Moments`] is asked for.
+    pub fn moments(&self) -> bool {
+        self.mean || self.variance || self.skewness || self.kurtosis
+    }
+
+    /// Quantiles to select, in order: the median, the percentiles, then P25 and P75 for the IQR.
+    pub fn quantiles(&self) -> Vec<f64> {
+        let mut qs = Vec::new();
+        if self.median {
+            qs.push(0.5);
+        }
+        qs.extend(self.percentiles.iter().map(|p| p / 100.0));
+        if self.iqr {
+            qs.extend([0.25, 0.75]);
+        }
+        qs
+    }
+}
+
+/// Everything found about one input.
+#[derive(Debug)]
+pub struct Report {
+    /// The input, as it was named.
+    pub path: String,
+    /// Whether the input has columns that are summarized one by one, rather
+    /// than all its values together.
+    pub per_column: bool,
+    /// One report per column, or a single one for all values together.
+    pub columns: Vec<ColumnReport>,
+    /// The covariance of every pair of columns, row by row.
+    pub covariance: Option<Vec<Vec<f64>>>,
+    /// The correlation of every pair of columns, row by row.
+    pub correlation: Option<Vec<Vec<f64>>>,
+    /// Values left out of the statistics.
+    pub skipped: Skipped,
+    /// Time taken by the first pass, which computes everything but exact
+    /// quantiles. The statistics are computed together, value by value, so
+    /// they have no timings of their own.
+    pub scan_time: Duration,
+    /// Time taken by the passes that select exact quantiles, if any.
+    pub select_time: Option<Duration>,
+    /// Time taken by all passes, from opening the input on.
+    pub total_time: Duration,
+}
+
+/// The statistics of one column, or of all values together. Statistics that
+/// were not asked for are `None` or empty, and so are those that need values
+/// when the column has none.
+#[derive(Debug)]
+pub struct ColumnReport {
+    /// `None` when all values are taken together.
+    pub name: Option<String>,
+    /// The number of values.
+    pub len: usize,
+    /// The smallest and the largest value.
+    pub min_max: Option<(f64, f64)>,
+    /// The mean, variance, skewness and kurtosis.
+    pub moments: Option<Moments>,
+    /// The middle value as both `lower` and `upper` for an odd count, or the
+    /// two middle values for an even one; see [`Interpolation::Midpoint`].
+    pub median: Option<Quantile>,
+    /// Each percentile asked for, with its value.
+    pub percentiles: Vec<(f64, Option<f64>)>,
+    /// The interquartile range.
+    pub iqr: Option<f64>,
+    /// Each approximate percentile asked for, with its estimate.
+    pub approx_percentiles: Vec<(f64, Option<f64>)>,
+    /// The rank error of the approximate percentiles.
+    pub rank_error: Option<f64>,
+    /// The first and the last values, both in input order.
+    pub tails: Option<(Vec<f64>, Vec<f64>)>,
+}
+
+impl Report {
+    /// Number of values in all columns together.
+    pub fn len(&self) -> usize {
+        self.columns.iter().map(|column| column.len).sum()
+    }
+
+    /// Whether no column has values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ColumnReport {
+    /// The count and every statistic asked for that is a single number, named
+    /// like `mean` or `std_dev`, with percentiles named like `p99.9` and `approx_p50`.
+    pub fn values(&self, stats: &Stats) -> Vec<(String, Option<f64>)> {
+        let mut values = vec![("count".to_string(), Some(self.len as f64))];
+        let mut add = |name: &str, value: Option<f64>, on: bool| {
+            if on {
+                values.push((name.to_string(), value));
+            }
+        };
+        add("min", self.min_max.map(|(min, _)| min), stats.min_max);
+        add("max", self.min_max.map(|(_, max)| max), stats.min_max);
+        let moments = self.moments.as_ref();
+        add("mean", moments.map(Moments::mean), stats.mean);
+        add("variance", moments.map(Moments::variance), stats.variance);
+        add("sample_variance", moments.map(Moments::sample_variance), stats.variance);
+        add("std_dev", moments.map(Moments::std_dev), stats.variance);
+        add("skewness", moments.map(Moments::skewness), stats.skewness);
+        add("kurtosis", moments.map(Moments::kurtosis), stats.kurtosis);
+        add("median", self.median.map(|median| median.value(Interpolation::Midpoint)), stats.median);
+        for &(p, value) in &self.percentiles {
+            add(&format!("p{p}"), value, true);
+        }
+        add("iqr", self.iqr, stats.iqr);
+        for &(p, value) in &self.approx_percentiles {
+            add(&format!("approx_p{p}"), value, true);
+        }
+        values
+    }
+}
+
+fn no_values(source: &Source) -> Error {
+    Error { path: source.name().to_string(), kind: ErrorKind::NoValues }
+}
+
+/// What the main scan gathers for one column.
+type Outputs = (
+    usize,
+    Option<Option<(f64, f64)>>,
+    Option<Moments>,
+    Option<(Vec<f64>, VecDeque<f64>)>,
+    Option<Histogram>,
+    Option<Kll>,
+);
+
+/// Computes `stats` over `source` in one scan, plus the passes that exact
+/// quantiles need.
+///
+/// An input whose values are taken together must have some, unless only
+/// statistics that make sense without values are asked for.
+pub fn summarize(source: &mut Source, stats: &Stats) -> Result<Report> {
+    summarize_with(source, stats, ()).map(|(report, ())| report)
+}
+
+/// Like [`summarize`], and computes `extra` during the same scan, so a
+/// statistic of one's own costs no pass of its own.
+///
+/// `extra` sees every value of every column; see [`Accumulator::push_column`]
+/// for telling the columns apart.
+pub fn summarize_with<A: Accumulator + Clone + Send>(
+    source: &mut Source,
+    stats: &Stats,
+    extra: A,
+) -> Result<(Report, A::Output)> {
+    let start_time = Instant::now();
+    let qs = stats.quantiles();
+    if !qs.is_empty() {
+        source.keep_for_rereads();
+    }
+
+    let column = || {
+        (
+            Len::default(),
+            stats.min_max.then(MinMax::default),
+            stats.moments().then(Moments::default),
+            stats.tails.map(Tails::new),
+            (!qs.is_empty()).then(Histogram::default),
+            (!stats.approx_percentiles.is_empty()).then(|| Kll::with_accuracy(stats.accuracy())),
+        )
+    };
+    let ((mut columns, comoments, extra), skipped): ((Vec<Outputs>, _, _), _) = scan(
+        source,
+        (Columns::new(column()), (stats.covariance || stats.correlation).then(Comoments::default), extra),
+    )?;
+    let scan_time = start_time.elapsed();
+    if comoments.is_some() && source.scattered_rows() {
+        let message = "covariance and correlation need an array in C order, not Fortran order";
+        return Err(ErrorKind::Npy(message.to_string()).at(source.name()));
+    }
+
+    let names = source.column_names().to_vec();
+    // Columns only show up once they have a value, but every named one is reported.
+    while columns.len() < names.len() {
+        columns.push(column().finish());
+    }
+    let per_column = source.options().per_column || !names.is_empty();
+    let needs_values = stats.min_max || stats.moments() || !qs.is_empty() || !stats.approx_percentiles.is_empty();
+    if !per_column && columns[0].0 == 0 && needs_values {
+        return Err(no_values(source));
+    }
+
+    let mut select_time = None;
+    let histograms: Option<Vec<Histogram>> = columns.iter_mut().map(|column| column.4.take()).collect();
+    let quantiles = match histograms {
+        Some(histograms) => {
+            let start = Instant::now();
+            let selected = quantiles(source, &histograms, &qs)?;
+            select_time = Some(start.elapsed());
+            selected
+        }
+        None => vec![Vec::new(); columns.len()],
+    };
+
+    let count = columns.len();
+    let matrix = |value: &dyn Fn(usize, usize) -> f64| -> Vec<Vec<f64>> {
+        (0..count).map(|i| (0..count).map(|j| value(i, j)).collect()).collect()
+    };
+    let covariance = comoments.as_ref().filter(|_| stats.covariance).map(|c| matrix(&|i, j| c.covariance(i, j)));
+    let correlation = comoments.as_ref().filter(|_| stats.correlation).map(|c| matrix(&|i, j| c.correlation(i, j)));
+
+    let columns = columns.into_iter().zip(quantiles).enumerate()
+        .map(|(i, ((len, min_max, moments, tails, _, sketch), quantiles))| {
+            // Empty when none were asked for, or the column has no values.
+            let mut quantiles = quantiles.into_iter();
+            let median = if stats.median { quantiles.next() } else { None };
+            let percentiles = stats.percentiles.iter()
+                .map(|&p| (p, quantiles.next().map(|quantile| quantile.value(stats.interpolation))))
+                .collect();
+            let iqr = match (stats.iqr, quantiles.next(), quantiles.next()) {
+                (true, Some(p25), Some(p75)) => Some(p75.value(stats.interpolation) - p25.value(stats.interpolation)),
+                _ => None,
+            };
+            let approx_percentiles = match &sketch {
+                Some(sketch) => stats.approx_percentiles.iter().map(|&p| (p, sketch.quantile(p / 100.0))).collect(),
+                None => Vec::new(),
+            };
+            ColumnReport {
+                name: per_column.then(|| names.get(i).cloned().unwrap_or_else(|| format!("column {}", i + 1))),
+                len,
+                min_max: min_max.flatten(),
+                moments: moments.filter(|_| len > 0),
+                median,
+                percentiles,
+                iqr,
+                approx_percentiles,
+                rank_error: sketch.map(|sketch| sketch.rank_error()),
+                tails: tails.map(|(left, right)| (left, right.into())),
+            }
+        })
+        .collect();
+
+    let report = Report {
+        path: source.name().to_string(),
+        per_column,
+        columns,
+        covariance,
+        correlation,
+        skipped,
+        scan_time,
+        select_time,
+        total_time: start_time.elapsed(),
+    };
+    Ok((report, extra))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{summarize, Stats};
+    use crate::file_stat::testing::{npy, TestFile};
+    use crate::file_stat::{Column, Format, OnError, ReadOptions, Report};
+
+    fn f64s(values: &[f64]) -> Vec<u8> {
+        values.iter().flat_map(|x| x.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn fortran_order_arrays_have_no_covariance() {
+        let options = || ReadOptions { format: Format::Npy, per_column: true, ..ReadOptions::default() };
+        let stats = Stats { mean: true, covariance: true, correlation: true, ..Stats::default() };
+        // [[1, 2], [2, 5], [3, 23]] in either order.
+        let c_order = TestFile::new(npy("<f8", false, "(3, 2)", &f64s(&[1.0, 2.0, 2.0, 5.0, 3.0, 23.0])));
+        let fortran_order = TestFile::new(npy("<f8", true, "(3, 2)", &f64s(&[1.0, 2.0, 3.0, 2.0, 5.0, 23.0])));
+
+        let report = summarize(&mut c_order.source(options()), &stats).unwrap();
+        assert_eq!(report.covariance, Some(vec![vec![1.0, 10.5], vec![10.5, 129.0]]));
+
+        let err = summarize(&mut fortran_order.source(options()), &stats).unwrap_err();
+        assert!(err.to_string().contains("not Fortran order"), "{err}");
+
+        // Statistics of single columns do not need rows.
+        let stats = Stats { mean: true, ..Stats::default() };
+        let means = |file: &TestFile| {
+            let report = summarize(&mut file.source(options()), &stats).unwrap();
+            report.columns.iter().map(|column| column.moments.as_ref().unwrap().mean()).collect::<Vec<_>>()
+        };
+        assert_eq!(means(&fortran_order), [2.0, 10.0]);
+        assert_eq!(means(&c_order), [2.0, 10.0]);
+
+        // A single column is stored the same way in either order.
+        let stats = Stats { covariance: true, ..Stats::default() };
+        let one_column = TestFile::new(npy("<f8", true, "(3,)", &f64s(&[1.0, 2.0, 3.0])));
+        let report = summarize(&mut one_column.source(options()), &stats).unwrap();
+        assert_eq!(report.covariance, Some(vec![vec![1.0]]));
+    }
+
+    /// Checks that column `b` of `report` is there, without values, and that
+    /// the matrices have a row and a column for it.
+    fn assert_empty_b(report: &Report) {
+        let names: Vec<_> = report.columns.iter().map(|column| column.name.as_deref()).collect();
+        assert_eq!(names, [Some("a"), Some("b")]);
+        let b = &report.columns[1];
+        assert_eq!(b.len, 0);
+        assert!(b.min_max.is_none() && b.moments.is_none() && b.median.is_none());
+        assert_eq!(b.percentiles, [(90.0, None)]);
+        for matrix in [report.covariance.as_ref().unwrap(), report.correlation.as_ref().unwrap()] {
+            assert_eq!(matrix.len(), 2);
+            assert_eq!(matrix[0][0], 1.0);
+            assert!(matrix[0][1].is_nan() && matrix[1][0].is_nan() && matrix[1][1].is_nan());
+        }
+    }
+
+    fn all_but_tails() -> Stats {
+        Stats { tails: None, percentiles: vec![90.0], ..Stats::all() }
+    }
+
+    #[test]
+    fn delimited_columns_without_values_are_reported() {
+        let file = TestFile::new("a,b\n1,NA\n2,NA\n3,\n");
+        let options = ReadOptions {
+            format: Format::Delimited { delimiter: b',' },
+            columns: vec![Column::Name("a".to_string()), Column::Name("b".to_string())],
+            on_error: OnError::Skip,
+            ..ReadOptions::default()
+        };
+        let report = summarize(&mut file.source(options), &Stats { covariance: true, correlation: true, ..all_but_tails() });
+        assert_empty_b(&report.unwrap());
+    }
+
+    #[test]
+    fn json_fields_without_values_are_reported() {
+        let file = TestFile::new("{\"a\": 1}\n{\"a\": 2, \"b\": null}\n{\"a\": 3, \"c\": 4}\n");
+        let options = ReadOptions {
+            format: Format::Jsonl,
+            fields: vec!["a".to_string(), "b".to_string()],
+            on_error: OnError::Skip,
+            ..ReadOptions::default()
+        };
+        let report = summarize(&mut file.source(options), &Stats { covariance: true, correlation: true, ..all_but_tails() });
+        assert_empty_b(&report.unwrap());
+    }
+}
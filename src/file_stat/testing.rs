@@ -0,0 +1,117 @@
+//! Inputs and random numbers for the unit tests.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{ReadOptions, Source};
+
+/// A file with the given contents in the system temporary directory,
+/// removed on drop.
+pub struct TestFile(PathBuf);
+
+impl TestFile {
+    pub fn new(contents: impl AsRef<[u8]>) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("bigfilestat-test-{}-{}", std::process::id(), n));
+        fs::write(&path, contents).unwrap();
+        TestFile(path)
+    }
+
+    /// A file of `values`, one per line, written so that they read back exactly.
+    pub fn with_values(values: &[f64]) -> Self {
+        TestFile::new(values.iter().map(|x| format!("{x:?}\n")).collect::<String>())
+    }
+
+    /// Replaces the contents, as a process writing the file would.
+    pub fn rewrite(&self, contents: impl AsRef<[u8]>) {
+        fs::write(&self.0, contents).unwrap();
+    }
+
+    pub fn source(&self, options: ReadOptions) -> Source {
+        Source::new(&self.0, options)
+    }
+}
+
+impl Drop for TestFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// A version 1 `.npy` file with the given header fields and data, padded
+/// the way numpy pads it.
+pub fn npy(descr: &str, fortran_order: bool, shape: &str, data: &[u8]) -> Vec<u8> {
+    let fortran_order = if fortran_order { "True" } else { "False" };
+    let mut dict = format!("{{'descr': '{descr}', 'fortran_order': {fortran_order}, 'shape': {shape}, }}");
+    while (10 + dict.len() + 1) % 64 != 0 {
+        dict.push(' ');
+    }
+    dict.push('\n');
+    let mut file = b"\x93NUMPY\x01\x00".to_vec();
+    file.extend((dict.len() as u16).to_le_bytes());
+    file.extend(dict.as_bytes());
+    file.extend(data);
+    file
+}
+
+/// A reader that gives at most a few bytes per read, and is interrupted
+/// now and then, so that records are cut at every possible place.
+pub struct Trickle<'a> {
+    data: &'a [u8],
+    rng: Rng,
+    max: usize,
+}
+
+impl<'a> Trickle<'a> {
+    pub fn new(data: &'a [u8], max: usize) -> Self {
+        Trickle { data, rng: Rng(max as u64 + 1), max }
+    }
+}
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.rng.below(8) == 0 {
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        let n = (1 + self.rng.below(self.max as u64) as usize).min(buf.len()).min(self.data.len());
+        buf[..n].copy_from_slice(&self.data[..n]);
+        self.data = &self.data[n..];
+        Ok(n)
+    }
+}
+
+/// xorshift64*, so the tests need no dependencies and are reproducible.
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn float(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// `len` values of a heavy-tailed mix: mostly small, some large, some negative.
+    pub fn values(&mut self, len: usize) -> Vec<f64> {
+        (0..len)
+            .map(|_| match self.below(10) {
+                0 => -self.float() * 1e3,
+                1 => 1.0 / (self.float() + 1e-9),
+                _ => self.float(),
+            })
+            .collect()
+    }
+}
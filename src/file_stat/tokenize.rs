@@ -0,0 +1,199 @@
+use std::fmt;
+use std::io::{self, Read};
+
+use super::buffer;
+
+/// Bytes that separate values. ASCII whitespace always does; more can be added.
+#[derive(Clone)]
+pub struct Separators {
+    set: [u64; 4],
+    /// The same bytes as `set`, listed for the vector kernels.
+    bytes: Vec<u8>,
+    kernel: Kernel,
+}
+
+/// How a block of 64 bytes is classified into separators and the rest.
+#[derive(Clone, Copy)]
+enum Kernel {
+    Scalar,
+    #[cfg(target_arch = "x86_64")]
+    Sse2,
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+}
+
+impl Kernel {
+    /// The fastest kernel this CPU supports.
+    fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") {
+                return Kernel::Avx2;
+            }
+            Kernel::Sse2
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        Kernel::Scalar
+    }
+}
+
+impl fmt::Debug for Separators {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Separators").field("bytes", &format_args!("\"{}\"", self.bytes.escape_ascii())).finish_non_exhaustive()
+    }
+}
+
+impl Separators {
+    /// ASCII whitespace and the bytes of `extra`.
+    pub fn new(extra: &[u8]) -> Self {
+        let mut set = [0u64; 4];
+        for b in (0..=255u8).filter(u8::is_ascii_whitespace).chain(extra.iter().copied()) {
+            set[(b >> 6) as usize] |= 1 << (b & 63);
+        }
+        let bytes = (0..=255u8).filter(|&b| set[(b >> 6) as usize] & (1 << (b & 63)) != 0).collect();
+        Separators { set, bytes, kernel: Kernel::detect() }
+    }
+
+    /// Classifies bytes one at a time instead of with vector instructions.
+    pub fn scalar(self) -> Self {
+        Separators { kernel: Kernel::Scalar, ..self }
+    }
+
+    /// Whether `b` separates values.
+    #[inline]
+    pub fn contains(&self, b: u8) -> bool {
+        self.set[(b >> 6) as usize] & (1 << (b & 63)) != 0
+    }
+
+    /// Bit `i` of the result is set when `block[i]` is a separator.
+    #[inline]
+    fn mask(&self, block: &[u8; 64]) -> u64 {
+        match self.kernel {
+            Kernel::Scalar => block.iter().enumerate().fold(0, |mask, (i, &b)| mask | (self.contains(b) as u64) << i),
+            // SAFETY: SSE2 is part of the x86_64 baseline.
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Sse2 => unsafe { x86::mask_sse2(block, &self.bytes) },
+            // SAFETY: `Kernel::detect` only picks AVX2 when the CPU has it.
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => unsafe { x86::mask_avx2(block, &self.bytes) },
+        }
+    }
+}
+
+impl Default for Separators {
+    fn default() -> Self {
+        Separators::new(&[])
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn mask_sse2(block: &[u8; 64], bytes: &[u8]) -> u64 {
+        let mut mask = 0;
+        for (i, lane) in block.chunks_exact(16).enumerate() {
+            let v = _mm_loadu_si128(lane.as_ptr().cast());
+            let mut hits = _mm_setzero_si128();
+            for &b in bytes {
+                hits = _mm_or_si128(hits, _mm_cmpeq_epi8(v, _mm_set1_epi8(b as i8)));
+            }
+            mask |= (_mm_movemask_epi8(hits) as u16 as u64) << (16 * i);
+        }
+        mask
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn mask_avx2(block: &[u8; 64], bytes: &[u8]) -> u64 {
+        let mut mask = 0;
+        for (i, lane) in block.chunks_exact(32).enumerate() {
+            let v = _mm256_loadu_si256(lane.as_ptr().cast());
+            let mut hits = _mm256_setzero_si256();
+            for &b in bytes {
+                hits = _mm256_or_si256(hits, _mm256_cmpeq_epi8(v, _mm256_set1_epi8(b as i8)));
+            }
+            mask |= (_mm256_movemask_epi8(hits) as u32 as u64) << (32 * i);
+        }
+        mask
+    }
+}
+
+/// Calls `action` with every token of `data` that ends before `data` does,
+/// and returns where the unfinished last token starts. At the end of the
+/// input (`eof`) the last token counts as finished.
+///
+/// `data` is classified 64 bytes at a time into a bit mask of separators, and
+/// token boundaries are found as the changes between set and clear bits.
+///
+/// `offset` is the position of `data` in the input.
+#[inline]
+fn split<E, F>(data: &[u8], eof: bool, offset: u64, separators: &Separators, action: &mut F) -> Result<usize, E>
+where
+    F: FnMut(&[u8], u64) -> Result<(), E>,
+{
+    let len = data.len();
+    // Start of the token that the last block ended in, if any.
+    let mut start = None;
+    let mut base = 0;
+    while base < len {
+        // The last block is padded with separators, which cannot start a token.
+        let seps = match data[base..].first_chunk::<64>() {
+            Some(block) => separators.mask(block),
+            None => {
+                let mut block = [b' '; 64];
+                block[..len - base].copy_from_slice(&data[base..]);
+                separators.mask(&block)
+            }
+        };
+
+        let mut pos = 0;
+        loop {
+            let edges = if start.is_some() { seps } else { !seps } & (u64::MAX << pos);
+            if edges == 0 {
+                break;
+            }
+            pos = edges.trailing_zeros();
+            let at = base + pos as usize;
+            match start {
+                Some(_) if at >= len => break,
+                Some(s) => {
+                    action(&data[s..at], offset + s as u64)?;
+                    start = None;
+                }
+                None => start = Some(at),
+            }
+        }
+        base += 64;
+    }
+
+    match start {
+        Some(s) if !eof => Ok(s),
+        Some(s) => {
+            action(&data[s..], offset + s as u64)?;
+            Ok(len)
+        }
+        None => Ok(len),
+    }
+}
+
+/// Calls `action` with every token of an input that is all in memory, such as
+/// a mapped file, without copying anything. `offset` is where `data` starts
+/// in the input.
+pub fn for_each_token_in<E, F>(data: &[u8], offset: u64, separators: &Separators, mut action: F) -> Result<(), E>
+where
+    F: FnMut(&[u8], u64) -> Result<(), E>,
+{
+    split(data, true, offset, separators, &mut action).map(|_| ())
+}
+
+/// Calls `action` with every token of `reader` and its byte offset, skipping
+/// runs of separators. Tokens of any length come out whole.
+pub fn for_each_token<R, E, F>(reader: R, separators: &Separators, mut action: F) -> Result<(), E>
+where
+    R: Read,
+    E: From<io::Error>,
+    F: FnMut(&[u8], u64) -> Result<(), E>,
+{
+    buffer::read_records(reader, |data, offset, eof| split(data, eof, offset, separators, &mut action))
+}
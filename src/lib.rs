@@ -0,0 +1,28 @@
+//! Statistics of the numbers in big files, computed in one pass where they
+//! can be and in as few passes as possible where they cannot.
+//!
+//! A [`Source`] names an input and says how to read values out of it with
+//! [`ReadOptions`]; [`summarize`] computes the [`Stats`] asked for into a
+//! [`Report`]:
+//!
+//! ```no_run
+//! use bigfilestat::{summarize, ReadOptions, Source, Stats};
+//!
+//! let mut source = Source::new("values.txt", ReadOptions::default());
+//! let stats = Stats { mean: true, percentiles: vec![99.0], ..Stats::default() };
+//! let report = summarize(&mut source, &stats)?;
+//! for column in &report.columns {
+//!     println!("{:?}", column.values(&stats));
+//! }
+//! # Ok::<(), bigfilestat::Error>(())
+//! ```
+//!
+//! Single statistics are [`Accumulator`]s, which [`scan`] feeds every value
+//! of an input. Statistics of one's own implement the trait as well, and
+//! [`summarize_with`] computes them alongside the report.
+
+#![warn(missing_docs, missing_debug_implementations)]
+
+mod file_stat;
+
+pub use file_stat::*;
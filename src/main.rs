@@ -1,155 +1,66 @@
-mod file_stat {
-    use std::cmp::{Ordering};
-    use std::collections::VecDeque;
-    use std::fs::File;
-    use std::io::{prelude::*, BufReader};
+mod cli;
+mod report;
 
-    pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+use bigfilestat::{OnWarning, Source};
+use std::process::exit;
 
-    fn for_file<F: FnMut(f64)>(filename: &str, mut action: F) -> Result<()> {
-        let file = File::open(filename)?;
-        let reader = BufReader::new(file);
 
-        for data in reader.split(' ' as u8) {
-            action(String::from_utf8(data?)?.parse::<f64>()?);
+fn main() {
+    let mut args = match cli::parse(std::env::args_os().skip(1)) {
+        Ok(cli::Command::Run(args)) => *args,
+        Ok(cli::Command::Help) => {
+            print!("{}", cli::USAGE);
+            return;
         }
+        Ok(cli::Command::Version) => {
+            println!("bigfilestat {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(err) => {
+            eprintln!("bigfilestat: {err}\nTry 'bigfilestat --help' for more information.");
+            exit(2);
+        }
+    };
 
-        Ok(())
-    }
-
-    pub fn min_max(filename: &str) -> Result<Option<(f64, f64)>> {
-        let mut val_min = None;
-        let mut val_max = None;
-
-        for_file(filename, |x| {
-            let _ = val_min.insert(x.min(val_min.unwrap_or(x)));
-            let _ = val_max.insert(x.max(val_max.unwrap_or(x)));
-        })?;
-
-        Ok(val_min.zip(val_max))
-    }
-
-    pub fn len(filename: &str) -> Result<usize> {
-        let mut size = 0;
-        for_file(filename, |_| size += 1)?;
-        Ok(size)
-    }
-
-    pub fn average(filename: &str) -> Result<f64> {
-        let mut sum = 0.0;
-        let mut len = 0u64;
-        for_file(filename, |x| {
-            sum += x;
-            len += 1;
-        })?;
-        Ok(sum / len as f64)
-    }
-
-    pub fn dispersion(filename: &str) -> Result<f64> {
-        let x_avr = average(filename)?;
-        let mut sum = 0.0;
-        let mut len = 0u64;
-        for_file(filename, |x| {
-            sum += (x - x_avr).powi(2);
-            len += 1;
-        })?;
-        Ok(sum / len as f64)
-    }
-
-
-    fn is_median(filename: &str, val: f64) -> std::result::Result<Ordering, Box<dyn std::error::Error>> {
-        let mut less = 0i64;
-        let mut eq = 0i64;
-        let mut greater = 0i64;
+    args.options.on_warning = Some(OnWarning::new(|warning| eprintln!("bigfilestat: warning: {warning}, skipped")));
 
-        for_file(filename, |x| {
-            match x.total_cmp(&val) {
-                Ordering::Less => { less += 1 }
-                Ordering::Equal => { eq += 1 }
-                Ordering::Greater => { greater += 1 }
+    let mut failed = false;
+    let mut reports = Vec::new();
+    for (i, path) in args.inputs.iter().enumerate() {
+        let filename = path.to_string_lossy();
+        if args.output == cli::Output::Text && args.inputs.len() > 1 {
+            if i > 0 {
+                println!();
             }
-        })?;
-
-        if (greater - less).abs() < eq + 1 {
-            Ok(Ordering::Equal)
-        } else if greater < less {
-            Ok(Ordering::Less)
-        } else {
-            Ok(Ordering::Greater)
+            println!("==> {filename} <==");
         }
-    }
-
-    fn find_median(filename: &str, left: f64, right: f64) -> Result<f64> {
-        if (left - right).abs() <= 0.001 {
-            return Ok(left);
+        let result = bigfilestat::summarize(&mut Source::new(path, args.options.clone()), &args.stats);
+        if let Err(err) = &result {
+            eprintln!("bigfilestat: {err}");
+            failed = true;
         }
-
-        let mid = (right + left) / 2.0;
-        match is_median(filename, mid)? {
-            Ordering::Less => { find_median(filename, left, mid) }
-            Ordering::Equal => { find_median(filename, left, mid) } // ??
-            Ordering::Greater => { find_median(filename, mid, right) }
+        match args.output {
+            cli::Output::Text => {
+                if let Ok(report) = result {
+                    report::print_text(&args.stats, &report);
+                }
+            }
+            cli::Output::Json | cli::Output::Table(_) | cli::Output::OpenMetrics => reports.push((filename.into_owned(), result)),
         }
     }
-
-    pub fn median(filename: &str) -> Result<f64> {
-        let (min, max) = min_max(filename)?.expect("median requires 1+ value");
-        find_median(filename, min, max)
-    }
-
-    pub fn tails(filename: &str, len: usize) -> Result<(Vec<f64>, VecDeque<f64>)> {
-        let mut left = Vec::new();
-        let mut right = VecDeque::new();
-
-        for_file(filename, |x| {
-            if left.len() < len {
-                left.push(x);
-            }
-
-            right.push_back(x);
-            if right.len() > len {
-                right.pop_front();
-            }
-        })?;
-
-        Ok((left, right))
+    match args.output {
+        cli::Output::Text => {}
+        cli::Output::Json => report::print_json(&args.stats, &reports),
+        cli::Output::Table(table) => report::print_rows(table, &args.stats, &reports),
+        cli::Output::OpenMetrics => report::print_openmetrics(&args.stats, &reports),
     }
-}
-
 
-fn elapsed() -> std::time::Duration {
-    unsafe {
-        static mut X: Option<std::time::Instant> = None;
-        if X.is_none() {
-            let _ = X.insert(std::time::Instant::now());
-        }
-        let ret = X.unwrap().elapsed();
-        let _ = X.insert(std::time::Instant::now());
-        ret
+    if failed {
+        exit(1);
     }
 }
 
 
-fn main() -> file_stat::Result<()> {
-    let start_time = std::time::Instant::now();
-    let filename = "testdata/bigfile.txt";
-    elapsed();
-
-    println!("LEN\t\t{}\t({:?})", file_stat::len(filename)?, elapsed());
-    println!("MIN, MAX\t{:?}\t({:?})", file_stat::min_max(filename)?.expect("no values"), elapsed());
-    println!("AVERAGE\t\t{}\t({:?})", file_stat::average(filename)?, elapsed());
-    println!("DISPERSION\t{}\t({:?})", file_stat::dispersion(filename)?, elapsed());
-    println!("MEDIAN\t\t{}\t({:?})", file_stat::median(filename)?, elapsed());
-    let (left, right) = file_stat::tails(filename, 10000)?;
-    println!("LEFT TAIL\t{:.3?}\t({:?})", left.iter().take(10).collect::<Vec<&f64>>(), elapsed());
-    println!("RIGHT TAIL\t{:.3?}", right.iter().rev().take(10).collect::<Vec<&f64>>());
-
-    println!("TIME TOOK\t{:?}", start_time.elapsed());
-
-    Ok(())
-}
-
-
 /* CHISQ(2)
 ~/me/spbu/rust/bigfilestat $ ./target/release/bigfilestat
     LEN             100_000_000                                                             (5.617566125s)
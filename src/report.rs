@@ -0,0 +1,9 @@
+mod json;
+mod openmetrics;
+mod table;
+mod text;
+
+pub use json::print_json;
+pub use openmetrics::print_openmetrics;
+pub use table::{print_rows, Table};
+pub use text::print_text;
@@ -0,0 +1,248 @@
+use std::fmt::Write;
+
+use bigfilestat::{ColumnReport, Error, Interpolation, Moments, Report, Stats};
+
+/// A JSON value, built up before it is printed.
+enum Json {
+    Null,
+    Number(f64),
+    Count(u64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl From<f64> for Json {
+    fn from(x: f64) -> Self {
+        Json::Number(x)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Self {
+        Json::Count(n as u64)
+    }
+}
+
+impl From<u64> for Json {
+    fn from(n: u64) -> Self {
+        Json::Count(n)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Json::String(s.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Self {
+        Json::String(s)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(items: Vec<T>) -> Self {
+        Json::Array(items.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Collects the members of an object in the order they are added.
+#[derive(Default)]
+struct Object(Vec<(String, Json)>);
+
+impl Object {
+    fn add(&mut self, key: impl Into<String>, value: impl Into<Json>) {
+        self.0.push((key.into(), value.into()));
+    }
+}
+
+impl From<Object> for Json {
+    fn from(object: Object) -> Self {
+        Json::Object(object.0)
+    }
+}
+
+impl Json {
+    fn is_scalar(&self) -> bool {
+        !matches!(self, Json::Array(_) | Json::Object(_))
+    }
+
+    /// Appends the value to `out`, one member per line, indented by `indent`
+    /// levels. Arrays of plain values stay on one line.
+    fn write(&self, out: &mut String, indent: usize) {
+        let pad = |out: &mut String, indent: usize| out.extend(std::iter::repeat_n("  ", indent));
+        match self {
+            // JSON has no NaN or infinity.
+            Json::Null => out.push_str("null"),
+            Json::Number(x) if !x.is_finite() => out.push_str("null"),
+            Json::Number(x) => write!(out, "{x:?}").unwrap(),
+            Json::Count(n) => write!(out, "{n}").unwrap(),
+            Json::String(s) => write_string(out, s),
+            Json::Array(items) if items.iter().all(Json::is_scalar) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    item.write(out, indent);
+                }
+                out.push(']');
+            }
+            Json::Array(items) => {
+                out.push_str("[\n");
+                for (i, item) in items.iter().enumerate() {
+                    pad(out, indent + 1);
+                    item.write(out, indent + 1);
+                    out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+                }
+                pad(out, indent);
+                out.push(']');
+            }
+            Json::Object(members) if members.is_empty() => out.push_str("{}"),
+            Json::Object(members) => {
+                out.push_str("{\n");
+                for (i, (key, value)) in members.iter().enumerate() {
+                    pad(out, indent + 1);
+                    write_string(out, key);
+                    out.push_str(": ");
+                    value.write(out, indent + 1);
+                    out.push_str(if i + 1 < members.len() { ",\n" } else { "\n" });
+                }
+                pad(out, indent);
+                out.push('}');
+            }
+        }
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Percentiles as an object keyed by the percentile, such as `"99.9"`.
+fn percentiles(values: &[(f64, Option<f64>)]) -> Object {
+    let mut object = Object::default();
+    for &(p, value) in values {
+        object.add(p.to_string(), value);
+    }
+    object
+}
+
+fn column(stats: &Stats, column: &ColumnReport) -> Object {
+    let mut object = Object::default();
+    object.add("name", column.name.clone());
+    object.add("count", column.len);
+    if stats.min_max {
+        object.add("min", column.min_max.map(|(min, _)| min));
+        object.add("max", column.min_max.map(|(_, max)| max));
+    }
+    let moments = column.moments.as_ref();
+    if stats.mean {
+        object.add("mean", moments.map(Moments::mean));
+    }
+    if stats.variance {
+        object.add("variance", moments.map(Moments::variance));
+        object.add("sample_variance", moments.map(Moments::sample_variance));
+        object.add("std_dev", moments.map(Moments::std_dev));
+    }
+    if stats.skewness {
+        object.add("skewness", moments.map(Moments::skewness));
+    }
+    if stats.kurtosis {
+        object.add("kurtosis", moments.map(Moments::kurtosis));
+    }
+    if stats.median {
+        object.add("median", column.median.map(|median| median.value(Interpolation::Midpoint)));
+    }
+    if !stats.percentiles.is_empty() {
+        object.add("percentiles", percentiles(&column.percentiles));
+    }
+    if stats.iqr {
+        object.add("iqr", column.iqr);
+    }
+    if !stats.approx_percentiles.is_empty() {
+        object.add("approx_percentiles", percentiles(&column.approx_percentiles));
+        object.add("rank_error", column.rank_error);
+    }
+    if let Some((left, right)) = &column.tails {
+        let mut tails = Object::default();
+        tails.add("first", left.clone());
+        tails.add("last", right.clone());
+        object.add("tails", tails);
+    }
+    object
+}
+
+/// The object of one input. Its `seconds` are per pass over the input, not
+/// per statistic: `scan` computes every statistic but exact percentiles at
+/// once, value by value, so there is no time that one of them alone takes;
+/// `select` is the passes that find exact percentiles, or null when there
+/// are none, and `total` includes opening the input.
+fn report(stats: &Stats, report: &Report) -> Object {
+    let mut object = Object::default();
+    object.add("path", report.path.as_str());
+    object.add("count", report.len());
+    let mut skipped = Object::default();
+    skipped.add("invalid", report.skipped.invalid);
+    skipped.add("nan", report.skipped.nan);
+    skipped.add("infinite", report.skipped.infinite);
+    object.add("skipped", skipped);
+    let columns: Vec<Json> = report.columns.iter().map(|c| column(stats, c).into()).collect();
+    object.add("columns", columns);
+    if let Some(covariance) = &report.covariance {
+        object.add("covariance", covariance.clone());
+    }
+    if let Some(correlation) = &report.correlation {
+        object.add("correlation", correlation.clone());
+    }
+    let mut timings = Object::default();
+    timings.add("scan", report.scan_time.as_secs_f64());
+    timings.add("select", report.select_time.map(|time| time.as_secs_f64()));
+    timings.add("total", report.total_time.as_secs_f64());
+    object.add("seconds", timings);
+    object
+}
+
+/// Prints the reports on all inputs as one JSON document, with the error
+/// instead of the statistics for inputs that failed.
+pub fn print_json(stats: &Stats, reports: &[(String, Result<Report, Error>)]) {
+    let inputs: Vec<Json> = reports.iter()
+        .map(|(path, result)| match result {
+            Ok(result) => report(stats, result).into(),
+            Err(err) => {
+                let mut object = Object::default();
+                object.add("path", path.as_str());
+                object.add("error", err.kind.to_string());
+                object.into()
+            }
+        })
+        .collect();
+    let mut document = Object::default();
+    document.add("tool", "bigfilestat");
+    document.add("version", env!("CARGO_PKG_VERSION"));
+    document.add("inputs", inputs);
+
+    let mut out = String::new();
+    Json::from(document).write(&mut out, 0);
+    println!("{out}");
+}
@@ -0,0 +1,255 @@
+use std::fmt::Write;
+
+use bigfilestat::{ColumnReport, Error, Interpolation, Moments, Report, Stats};
+
+const PREFIX: &str = "bigfilestat_";
+
+/// A label value with the characters that end or break it escaped.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn number(x: f64) -> String {
+    match x {
+        x if x.is_nan() => "NaN".to_string(),
+        f64::INFINITY => "+Inf".to_string(),
+        f64::NEG_INFINITY => "-Inf".to_string(),
+        x => format!("{x:?}"),
+    }
+}
+
+/// Collects the text of the metric families, each family in one piece.
+struct Metrics(String);
+
+impl Metrics {
+    fn family(&mut self, name: &str, kind: &str, unit: Option<&str>, help: &str) {
+        writeln!(self.0, "# TYPE {PREFIX}{name} {kind}").unwrap();
+        if let Some(unit) = unit {
+            writeln!(self.0, "# UNIT {PREFIX}{name} {unit}").unwrap();
+        }
+        writeln!(self.0, "# HELP {PREFIX}{name} {help}").unwrap();
+    }
+
+    /// Adds a sample of metric `name`, with `labels` already formatted as `key="value"`.
+    fn sample(&mut self, name: &str, labels: &[String], value: &str) {
+        writeln!(self.0, "{PREFIX}{name}{{{}}} {value}", labels.join(",")).unwrap();
+    }
+}
+
+/// The labels that tell an input, and a column of it, apart.
+fn labels(report: &Report, column: &ColumnReport) -> Vec<String> {
+    let mut labels = vec![format!("file=\"{}\"", escape(&report.path))];
+    if let Some(name) = &column.name {
+        labels.push(format!("column=\"{}\"", escape(name)));
+    }
+    labels
+}
+
+/// The quantile of percentile `p`. Rounding keeps `99.9` from becoming
+/// `0.9990000000000001` in the label.
+fn quantile(p: f64) -> f64 {
+    (p / 100.0 * 1e12).round() / 1e12
+}
+
+type Gauge = (&'static str, &'static str, fn(&Stats) -> bool, fn(&ColumnReport) -> Option<f64>);
+
+const GAUGES: [Gauge; 8] = [
+    ("min", "Smallest value.", |stats| stats.min_max, |column| column.min_max.map(|(min, _)| min)),
+    ("max", "Largest value.", |stats| stats.min_max, |column| column.min_max.map(|(_, max)| max)),
+    ("mean", "Arithmetic mean.", |stats| stats.mean, |column| column.moments.as_ref().map(Moments::mean)),
+    ("variance", "Population variance.", |stats| stats.variance, |column| column.moments.as_ref().map(Moments::variance)),
+    ("std_dev", "Population standard deviation.", |stats| stats.variance, |column| column.moments.as_ref().map(Moments::std_dev)),
+    ("skewness", "Skewness.", |stats| stats.skewness, |column| column.moments.as_ref().map(Moments::skewness)),
+    ("kurtosis", "Excess kurtosis.", |stats| stats.kurtosis, |column| column.moments.as_ref().map(Moments::kurtosis)),
+    ("iqr", "Interquartile range.", |stats| stats.iqr, |column| column.iqr),
+];
+
+/// Prints the reports that did not fail as OpenMetrics text, which the
+/// Prometheus node_exporter textfile collector reads as well.
+///
+/// The count, the sum and the exact quantiles of each column make up a
+/// summary; every other statistic is a gauge of its own. The sum comes with
+/// the moments, so the command line asks for the mean with this output.
+/// Samples carry the input as the `file` label and, for inputs with columns,
+/// the `column` label.
+pub fn print_openmetrics(stats: &Stats, reports: &[(String, Result<Report, Error>)]) {
+    print!("{}", openmetrics(stats, reports));
+}
+
+fn openmetrics(stats: &Stats, reports: &[(String, Result<Report, Error>)]) -> String {
+    let reports: Vec<&Report> = reports.iter().filter_map(|(_, result)| result.as_ref().ok()).collect();
+    let columns: Vec<(&Report, &ColumnReport)> =
+        reports.iter().flat_map(|&report| report.columns.iter().map(move |column| (report, column))).collect();
+    let mut metrics = Metrics(String::new());
+
+    metrics.family("values", "summary", None, "Values read from the input.");
+    for &(report, column) in &columns {
+        let labels = labels(report, column);
+        let mut quantiles: Vec<(f64, f64)> = column.median
+            .map(|median| (0.5, median.value(Interpolation::Midpoint)))
+            .into_iter()
+            .chain(column.percentiles.iter().filter_map(|&(p, value)| Some((quantile(p), value?))))
+            .collect();
+        // Each quantile once, even if asked for twice or both as the median and P50.
+        quantiles.sort_by(|a, b| a.0.total_cmp(&b.0));
+        quantiles.dedup_by(|a, b| a.0 == b.0);
+        for (q, value) in quantiles {
+            let labels: Vec<String> = labels.iter().cloned().chain([format!("quantile=\"{q}\"")]).collect();
+            metrics.sample("values", &labels, &number(value));
+        }
+        metrics.sample("values_count", &labels, &column.len.to_string());
+        if stats.moments() {
+            // Columns without values have no moments, and a sum of 0.
+            metrics.sample("values_sum", &labels, &number(column.moments.as_ref().map_or(0.0, Moments::sum)));
+        }
+    }
+
+    for (name, help, on, value) in GAUGES {
+        if !on(stats) {
+            continue;
+        }
+        metrics.family(name, "gauge", None, help);
+        for &(report, column) in &columns {
+            if let Some(value) = value(column) {
+                metrics.sample(name, &labels(report, column), &number(value));
+            }
+        }
+    }
+
+    if !stats.approx_percentiles.is_empty() {
+        metrics.family("approx_percentile", "gauge", None, "Percentile estimated from a KLL sketch.");
+        for &(report, column) in &columns {
+            for &(p, value) in &column.approx_percentiles {
+                if let Some(value) = value {
+                    let labels: Vec<String> = labels(report, column).into_iter().chain([format!("percentile=\"{p}\"")]).collect();
+                    metrics.sample("approx_percentile", &labels, &number(value));
+                }
+            }
+        }
+    }
+
+    metrics.family("skipped", "gauge", None, "Values left out of the statistics, by reason.");
+    for report in &reports {
+        let file = format!("file=\"{}\"", escape(&report.path));
+        let skipped = report.skipped;
+        for (reason, count) in [("invalid", skipped.invalid), ("nan", skipped.nan), ("infinite", skipped.infinite)] {
+            metrics.sample("skipped", &[file.clone(), format!("reason=\"{reason}\"")], &count.to_string());
+        }
+    }
+
+    metrics.family("duration_seconds", "gauge", Some("seconds"), "Time taken to compute the statistics of the input.");
+    for report in &reports {
+        let file = format!("file=\"{}\"", escape(&report.path));
+        metrics.sample("duration_seconds", &[file], &number(report.total_time.as_secs_f64()));
+    }
+
+    metrics.0.push_str("# EOF\n");
+    metrics.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::time::Duration;
+
+    use bigfilestat::{Accumulator, ColumnReport, Error, ErrorKind, Moments, Quantile, Report, Skipped, Stats};
+
+    use super::openmetrics;
+
+    fn column(name: Option<&str>, values: &[f64]) -> ColumnReport {
+        let mut moments = Moments::default();
+        values.iter().for_each(|&x| moments.push(x));
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let index = sorted.len() / 2;
+        let median = sorted.get(index).map(|&x| Quantile { index: index as u64, fraction: 0.0, lower: x, upper: x });
+        ColumnReport {
+            name: name.map(str::to_string),
+            len: values.len(),
+            min_max: sorted.first().zip(sorted.last()).map(|(&min, &max)| (min, max)),
+            moments: (!values.is_empty()).then_some(moments),
+            median,
+            percentiles: vec![(99.9, sorted.last().copied())],
+            iqr: None,
+            approx_percentiles: Vec::new(),
+            rank_error: None,
+            tails: None,
+        }
+    }
+
+    fn report(path: &str, columns: Vec<ColumnReport>, skipped: Skipped, seconds: f64) -> Report {
+        Report {
+            path: path.to_string(),
+            per_column: columns[0].name.is_some(),
+            columns,
+            covariance: None,
+            correlation: None,
+            skipped,
+            scan_time: Duration::from_secs_f64(seconds),
+            select_time: None,
+            total_time: Duration::from_secs_f64(seconds),
+        }
+    }
+
+    #[test]
+    fn golden_output() {
+        let stats = Stats { min_max: true, mean: true, median: true, percentiles: vec![99.9], ..Stats::default() };
+        // The input that failed is left out; the last one has a backslash,
+        // quotes and a newline to escape, and a column without values.
+        let missing = Error { path: "missing".to_string(), kind: ErrorKind::Io(io::ErrorKind::NotFound.into()) };
+        let reports = [
+            ("plain.txt".to_string(), Ok(report("plain.txt", vec![column(None, &[3.0, 1.0, 2.0])], Skipped::default(), 0.25))),
+            ("missing".to_string(), Err(missing)),
+            (
+                "dir\\\"odd\"\nname.csv".to_string(),
+                Ok(report(
+                    "dir\\\"odd\"\nname.csv",
+                    vec![column(Some("a \"b\""), &[4.0, -1.5]), column(Some("empty"), &[])],
+                    Skipped { invalid: 2, nan: 1, infinite: 0 },
+                    1.5,
+                )),
+            ),
+        ];
+        let expected = concat!(
+            "# TYPE bigfilestat_values summary\n",
+            "# HELP bigfilestat_values Values read from the input.\n",
+            r#"bigfilestat_values{file="plain.txt",quantile="0.5"} 2.0"#, "\n",
+            r#"bigfilestat_values{file="plain.txt",quantile="0.999"} 3.0"#, "\n",
+            r#"bigfilestat_values_count{file="plain.txt"} 3"#, "\n",
+            r#"bigfilestat_values_sum{file="plain.txt"} 6.0"#, "\n",
+            r#"bigfilestat_values{file="dir\\\"odd\"\nname.csv",column="a \"b\"",quantile="0.5"} 4.0"#, "\n",
+            r#"bigfilestat_values{file="dir\\\"odd\"\nname.csv",column="a \"b\"",quantile="0.999"} 4.0"#, "\n",
+            r#"bigfilestat_values_count{file="dir\\\"odd\"\nname.csv",column="a \"b\""} 2"#, "\n",
+            r#"bigfilestat_values_sum{file="dir\\\"odd\"\nname.csv",column="a \"b\""} 2.5"#, "\n",
+            r#"bigfilestat_values_count{file="dir\\\"odd\"\nname.csv",column="empty"} 0"#, "\n",
+            r#"bigfilestat_values_sum{file="dir\\\"odd\"\nname.csv",column="empty"} 0.0"#, "\n",
+            "# TYPE bigfilestat_min gauge\n",
+            "# HELP bigfilestat_min Smallest value.\n",
+            r#"bigfilestat_min{file="plain.txt"} 1.0"#, "\n",
+            r#"bigfilestat_min{file="dir\\\"odd\"\nname.csv",column="a \"b\""} -1.5"#, "\n",
+            "# TYPE bigfilestat_max gauge\n",
+            "# HELP bigfilestat_max Largest value.\n",
+            r#"bigfilestat_max{file="plain.txt"} 3.0"#, "\n",
+            r#"bigfilestat_max{file="dir\\\"odd\"\nname.csv",column="a \"b\""} 4.0"#, "\n",
+            "# TYPE bigfilestat_mean gauge\n",
+            "# HELP bigfilestat_mean Arithmetic mean.\n",
+            r#"bigfilestat_mean{file="plain.txt"} 2.0"#, "\n",
+            r#"bigfilestat_mean{file="dir\\\"odd\"\nname.csv",column="a \"b\""} 1.25"#, "\n",
+            "# TYPE bigfilestat_skipped gauge\n",
+            "# HELP bigfilestat_skipped Values left out of the statistics, by reason.\n",
+            r#"bigfilestat_skipped{file="plain.txt",reason="invalid"} 0"#, "\n",
+            r#"bigfilestat_skipped{file="plain.txt",reason="nan"} 0"#, "\n",
+            r#"bigfilestat_skipped{file="plain.txt",reason="infinite"} 0"#, "\n",
+            r#"bigfilestat_skipped{file="dir\\\"odd\"\nname.csv",reason="invalid"} 2"#, "\n",
+            r#"bigfilestat_skipped{file="dir\\\"odd\"\nname.csv",reason="nan"} 1"#, "\n",
+            r#"bigfilestat_skipped{file="dir\\\"odd\"\nname.csv",reason="infinite"} 0"#, "\n",
+            "# TYPE bigfilestat_duration_seconds gauge\n",
+            "# UNIT bigfilestat_duration_seconds seconds\n",
+            "# HELP bigfilestat_duration_seconds Time taken to compute the statistics of the input.\n",
+            r#"bigfilestat_duration_seconds{file="plain.txt"} 0.25"#, "\n",
+            r#"bigfilestat_duration_seconds{file="dir\\\"odd\"\nname.csv"} 1.5"#, "\n",
+            "# EOF\n",
+        );
+        assert_eq!(openmetrics(&stats, &reports), expected);
+    }
+}
@@ -0,0 +1,97 @@
+use bigfilestat::{Error, Report, Stats};
+
+/// A table format with one row per input, or per column of an input.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Table {
+    Csv,
+    Tsv,
+    Markdown,
+}
+
+impl Table {
+    /// `cell` as it can be written into the table.
+    fn escape(self, cell: &str) -> String {
+        match self {
+            Table::Csv if cell.contains([',', '"', '\n', '\r']) => format!("\"{}\"", cell.replace('"', "\"\"")),
+            Table::Csv => cell.to_string(),
+            // TSV has no quoting, so what would split the cell becomes a space.
+            Table::Tsv => cell.replace(['\t', '\n', '\r'], " "),
+            Table::Markdown => cell.replace('|', "\\|").replace(['\n', '\r'], " "),
+        }
+    }
+
+    fn print(self, header: &[String], rows: &[Vec<String>], text_columns: usize) {
+        match self {
+            Table::Csv | Table::Tsv => {
+                let delimiter = if self == Table::Csv { "," } else { "\t" };
+                for row in std::iter::once(header).chain(rows.iter().map(Vec::as_slice)) {
+                    let cells: Vec<String> = row.iter().map(|cell| self.escape(cell)).collect();
+                    println!("{}", cells.join(delimiter));
+                }
+            }
+            Table::Markdown => {
+                let header: Vec<String> = header.iter().map(|cell| self.escape(cell)).collect();
+                let rows: Vec<Vec<String>> =
+                    rows.iter().map(|row| row.iter().map(|cell| self.escape(cell)).collect()).collect();
+                let mut widths: Vec<usize> = header.iter().map(|cell| cell.chars().count().max(3)).collect();
+                for row in &rows {
+                    for (width, cell) in widths.iter_mut().zip(row) {
+                        *width = cell.chars().count().max(*width);
+                    }
+                }
+                // Names to the left, numbers to the right.
+                let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+                let align = |i: usize, cell: &str, width: usize| {
+                    if i < text_columns { format!("{cell:<width$}") } else { format!("{cell:>width$}") }
+                };
+                println!("{}", line(header.iter().zip(&widths).enumerate().map(|(i, (cell, &width))| align(i, cell, width)).collect()));
+                println!(
+                    "{}",
+                    line(widths.iter().enumerate()
+                        .map(|(i, &width)| if i < text_columns {
+                            format!(":{}", "-".repeat(width - 1))
+                        } else {
+                            format!("{}:", "-".repeat(width - 1))
+                        })
+                        .collect())
+                );
+                for row in rows {
+                    println!("{}", line(row.iter().zip(&widths).enumerate().map(|(i, (cell, &width))| align(i, cell, width)).collect()));
+                }
+            }
+        }
+    }
+}
+
+/// Prints the reports that did not fail as one table: a row per input, or per
+/// column of an input that has columns, and a column per statistic that is a
+/// single number. Tails and matrices do not fit into it and are left out.
+pub fn print_rows(table: Table, stats: &Stats, reports: &[(String, Result<Report, Error>)]) {
+    let reports: Vec<&Report> = reports.iter().filter_map(|(_, result)| result.as_ref().ok()).collect();
+    let Some(first) = reports.first().and_then(|report| report.columns.first()) else {
+        return;
+    };
+    let per_column = reports.iter().any(|report| report.per_column);
+
+    let mut header = vec!["file".to_string()];
+    if per_column {
+        header.push("column".to_string());
+    }
+    header.extend(first.values(stats).into_iter().map(|(name, _)| name));
+    header.push("skipped".to_string());
+
+    let rows: Vec<Vec<String>> = reports.iter()
+        .flat_map(|report| {
+            report.columns.iter().map(move |column| {
+                let mut row = vec![report.path.clone()];
+                if per_column {
+                    row.push(column.name.clone().unwrap_or_default());
+                }
+                row.extend(column.values(stats).into_iter().map(|(_, value)| value.map_or(String::new(), |value| value.to_string())));
+                row.push(report.skipped.total().to_string());
+                row
+            })
+        })
+        .collect();
+    table.print(&header, &rows, if per_column { 2 } else { 1 });
+}
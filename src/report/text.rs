@@ -0,0 +1,183 @@
+use bigfilestat::{ColumnReport, Interpolation, Moments, Report, Stats};
+
+/// Prints `report` for people to read.
+pub fn print_text(stats: &Stats, report: &Report) {
+    println!("SCAN\t\t{:?}", report.scan_time);
+    if let Some(select_time) = report.select_time {
+        println!("SELECT\t\t{:?}", select_time);
+    }
+
+    if report.per_column {
+        print_columns(stats, &report.columns);
+    } else {
+        for column in &report.columns {
+            print_column(stats, column);
+        }
+    }
+    let names: Vec<&str> = report.columns.iter().map(|column| column.name.as_deref().unwrap_or("")).collect();
+    if let Some(covariance) = &report.covariance {
+        print_matrix("COVARIANCE", &names, covariance);
+    }
+    if let Some(correlation) = &report.correlation {
+        print_matrix("CORRELATION", &names, correlation);
+    }
+
+    let skipped = report.skipped;
+    if skipped.total() > 0 {
+        println!(
+            "SKIPPED\t\t{} ({} invalid, {} NaN, {} infinite)",
+            skipped.total(), skipped.invalid, skipped.nan, skipped.infinite,
+        );
+    }
+
+    println!("TIME TOOK\t{:?}", report.total_time);
+}
+
+/// Prints `rows` under `header` in aligned columns, the first to the left
+/// and the rest, which hold numbers, to the right.
+fn print_table(header: Vec<String>, rows: Vec<Vec<String>>) {
+    let mut widths = vec![0; header.len()];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = cell.chars().count().max(*width);
+        }
+    }
+    for row in std::iter::once(header).chain(rows) {
+        let line: Vec<String> = row.iter().zip(&widths).enumerate()
+            .map(|(i, (cell, &width))| if i == 0 { format!("{cell:<width$}") } else { format!("{cell:>width$}") })
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+}
+
+fn print_matrix(title: &str, names: &[&str], matrix: &[Vec<f64>]) {
+    let header = std::iter::once(title).chain(names.iter().copied()).map(str::to_string).collect();
+    let rows = names.iter().zip(matrix)
+        .map(|(name, row)| std::iter::once(name.to_string()).chain(row.iter().map(f64::to_string)).collect())
+        .collect();
+    print_table(header, rows);
+}
+
+/// A table cell for a value that a column without values does not have.
+fn cell(value: Option<f64>) -> String {
+    value.map_or("-".to_string(), |value| value.to_string())
+}
+
+/// Prints one row per column, with a column per statistic. Tails do not fit
+/// into a table, so they follow it.
+fn print_columns(stats: &Stats, columns: &[ColumnReport]) {
+    let mut header = vec!["COLUMN".to_string()];
+    let mut add = |name: String, on: bool| {
+        if on {
+            header.push(name);
+        }
+    };
+    add("LEN".to_string(), stats.len);
+    add("MIN".to_string(), stats.min_max);
+    add("MAX".to_string(), stats.min_max);
+    add("AVERAGE".to_string(), stats.mean);
+    add("VARIANCE".to_string(), stats.variance);
+    add("STD DEV".to_string(), stats.variance);
+    add("SKEWNESS".to_string(), stats.skewness);
+    add("KURTOSIS".to_string(), stats.kurtosis);
+    add("MEDIAN".to_string(), stats.median);
+    for p in &stats.percentiles {
+        add(format!("P{p}"), true);
+    }
+    add("IQR".to_string(), stats.iqr);
+    for p in &stats.approx_percentiles {
+        add(format!("~P{p}"), true);
+    }
+
+    let rows = columns.iter()
+        .map(|column| {
+            let mut row = vec![column.name.clone().unwrap_or_default()];
+            if stats.len {
+                row.push(column.len.to_string());
+            }
+            if stats.min_max {
+                row.push(cell(column.min_max.map(|(min, _)| min)));
+                row.push(cell(column.min_max.map(|(_, max)| max)));
+            }
+            let moments = column.moments.as_ref();
+            let mut add = |value: Option<f64>, on: bool| {
+                if on {
+                    row.push(cell(value));
+                }
+            };
+            add(moments.map(Moments::mean), stats.mean);
+            add(moments.map(Moments::variance), stats.variance);
+            add(moments.map(Moments::std_dev), stats.variance);
+            add(moments.map(Moments::skewness), stats.skewness);
+            add(moments.map(Moments::kurtosis), stats.kurtosis);
+            if stats.median {
+                row.push(cell(column.median.map(|median| median.value(Interpolation::Midpoint))));
+            }
+            row.extend(column.percentiles.iter().map(|&(_, value)| cell(value)));
+            if stats.iqr {
+                row.push(cell(column.iqr));
+            }
+            row.extend(column.approx_percentiles.iter().map(|&(_, value)| cell(value)));
+            row
+        })
+        .collect();
+    if header.len() > 1 {
+        print_table(header, rows);
+    }
+
+    for column in columns {
+        if let Some((left, right)) = &column.tails {
+            let name = column.name.as_deref().unwrap_or_default();
+            println!("LEFT TAIL\t{name}\t{:.3?}", left);
+            println!("RIGHT TAIL\t{name}\t{:.3?}", right.iter().rev().collect::<Vec<&f64>>());
+        }
+    }
+}
+
+/// Prints the statistics of all values together, one per line.
+fn print_column(stats: &Stats, column: &ColumnReport) {
+    if stats.len {
+        println!("LEN\t\t{}", column.len);
+    }
+    if let Some(min_max) = column.min_max {
+        println!("MIN, MAX\t{:?}", min_max);
+    }
+    if let Some(moments) = &column.moments {
+        if stats.mean {
+            println!("AVERAGE\t\t{}", moments.mean());
+        }
+        if stats.variance {
+            println!("DISPERSION\t{} (sample {})", moments.variance(), moments.sample_variance());
+            println!("STD DEV\t\t{}", moments.std_dev());
+        }
+        if stats.skewness {
+            println!("SKEWNESS\t{}", moments.skewness());
+        }
+        if stats.kurtosis {
+            println!("KURTOSIS\t{}", moments.kurtosis());
+        }
+    }
+    if let Some(median) = column.median {
+        if median.lower == median.upper {
+            println!("MEDIAN\t\t{}", median.lower);
+        } else {
+            println!(
+                "MEDIAN\t\t{} (lower {}, upper {})",
+                median.value(Interpolation::Midpoint), median.lower, median.upper,
+            );
+        }
+    }
+    for (p, value) in &column.percentiles {
+        println!("P{}\t\t{}", p, cell(*value));
+    }
+    if let Some(iqr) = column.iqr {
+        println!("IQR\t\t{}", iqr);
+    }
+    for (p, value) in &column.approx_percentiles {
+        println!("~P{}\t\t{}\t(±{:.3}% rank)", p, cell(*value), 100.0 * column.rank_error.unwrap_or_default());
+    }
+    if let Some((left, right)) = &column.tails {
+        println!("LEFT TAIL\t{:.3?}", left);
+        println!("RIGHT TAIL\t{:.3?}", right.iter().rev().collect::<Vec<&f64>>());
+    }
+}
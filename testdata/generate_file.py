@@ -3,4 +3,6 @@
 import random
 import numpy as np
 
-open("bigfile.txt", "w").write(' '.join(list(map(lambda x: str(x), np.random.chisquare(2, 100_000_000)))))
\ No newline at end of file
+values = np.random.chisquare(2, 100_000_000)
+open("bigfile.txt", "w").write(' '.join(list(map(lambda x: str(x), values))))
+np.save("bigfile.npy", values)
\ No newline at end of file